@@ -1,6 +1,5 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn;
 
 #[proc_macro_derive(ToTag)]
 pub fn to_tag_derive(input: TokenStream) -> TokenStream {
@@ -38,14 +37,15 @@ fn impl_from_tag(ast: &syn::DeriveInput) -> Result<TokenStream, syn::Error> {
     match &ast.data {
         syn::Data::Enum(syn::DataEnum { variants, .. }) => {
             for variant in variants.iter() {
-                if variant.discriminant.is_none() {
+                // variants holding data can't be built from a tag alone
+                if variant.discriminant.is_none() || variant.fields != syn::Fields::Unit {
                     continue;
                 }
 
                 let variant_ident = &variant.ident;
 
                 match_variants.extend(quote! {
-                    x if x == (#name::#variant_ident).to_tag() => #name::#variant_ident,
+                    x if x == (#name::#variant_ident).to_tag() => Some(#name::#variant_ident),
                 });
             }
         }
@@ -65,10 +65,10 @@ fn impl_from_tag(ast: &syn::DeriveInput) -> Result<TokenStream, syn::Error> {
 
     let gen = quote! {
         impl tag::FromTag for #name {
-            fn from_tag(tag: u8) -> #name {
+            fn try_from_tag(tag: u8) -> Option<#name> {
                 match tag {
                     #match_variants
-                    _ => panic!("Some variant does not have a explicit discriminant."),
+                    _ => None,
                 }
             }
         }
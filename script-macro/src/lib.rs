@@ -1,64 +1,512 @@
-// This is a really naive implementation with almost no error handling,
-// but should be enough for our purposes.
-use hex;
-use proc_macro::{TokenStream, TokenTree};
-use quote::quote;
-use std::iter::Iterator;
-use std::panic::panic_any;
-use std::str::FromStr;
-use wallet::{FromScript, Script};
-
-fn get_pushdata(val: &str, iterator: &mut impl Iterator<Item = TokenTree>) -> Script {
-    let data_size = hex::decode(val).unwrap();
-
-    if let Some(next_val) = iterator.next() {
-        let hex_val = match next_val {
-            TokenTree::Ident(i) => i.to_string(),
-            TokenTree::Literal(l) => l.to_string(),
-            _ => panic!("next_val must be a hex."),
-        };
+// A naive implementation, but should be enough for our purposes. Opcodes,
+// numbers and `0x` raw bytes are read like Core's test ASM.
+use proc_macro2::{Delimiter, Ident, Span, TokenStream, TokenTree};
+use quote::{quote, quote_spanned};
+use std::iter::{Iterator, Peekable};
+use wallet::{AsmParseErrorKind, FromAsm, FromScript, PushBytes, PushEncoding, Script};
+
+type Result<T> = std::result::Result<T, syn::Error>;
+
+#[derive(Debug)]
+enum Item {
+    // serialized already
+    Literal(Vec<u8>),
+    // `{expr}`, pushed at runtime
+    Interpolated(TokenStream),
+}
+
+fn error<T>(span: Span, message: String) -> Result<T> {
+    Err(syn::Error::new(span, message))
+}
+
+fn combine(errors: Vec<syn::Error>) -> Option<syn::Error> {
+    let mut errors = errors.into_iter();
+    let mut first = errors.next()?;
+    first.extend(errors);
+
+    Some(first)
+}
+
+// Hex that Rust can't lex, like `021e` taken for an exponent or `0b12` for a
+// binary number, can be written `0x021e` or `"021e"`.
+fn hex_token(token: &TokenTree) -> Result<Vec<u8>> {
+    let hex_val = match token {
+        TokenTree::Ident(i) => i.to_string(),
+        TokenTree::Literal(l) => match syn::parse2::<syn::LitStr>(token.clone().into()) {
+            Ok(string) => string.value(),
+            Err(_) => {
+                let literal = l.to_string();
+                match literal.strip_prefix("0x") {
+                    Some(hex_val) => hex_val.to_string(),
+                    None => literal,
+                }
+            }
+        },
+        _ => return error(token.span(), format!("expected hex, found `{}`", token)),
+    };
 
-        Script::Data(data_size, hex::decode(hex_val).unwrap())
-    } else {
-        panic!("next_val must be a hex.");
+    hex::decode(&hex_val).or_else(|err| match err {
+        hex::FromHexError::OddLength => error(
+            token.span(),
+            format!("`{}` has an odd number of hex digits", hex_val),
+        ),
+        _ => error(token.span(), format!("expected hex, found `{}`", hex_val)),
+    })
+}
+
+// The data between `<` and `>`, given as any number of hex tokens.
+fn get_push(open: &TokenTree, iterator: &mut impl Iterator<Item = TokenTree>) -> Result<Vec<u8>> {
+    let mut tokens = Vec::new();
+    loop {
+        match iterator.next() {
+            Some(token) if is_punct(&token, '>') => break,
+            Some(token) => tokens.push(token),
+            None => return error(open.span(), "expected `>` to close this push".to_string()),
+        }
     }
+
+    let mut data = Vec::new();
+    for token in tokens.iter() {
+        data.extend(hex_token(token)?);
+    }
+
+    Ok(data)
 }
 
-#[proc_macro]
-pub fn script(input: TokenStream) -> TokenStream {
-    if input.is_empty() {
-        panic!("TokenStream is empty.");
+// The push MINIMALDATA accepts, the same as for `{expr}` values.
+fn push(data: &[u8]) -> Vec<u8> {
+    [Script::push(data)].from_script()
+}
+
+fn is_punct(token: &TokenTree, c: char) -> bool {
+    matches!(token, TokenTree::Punct(p) if p.as_char() == c)
+}
+
+fn pushdata_encoding(name: &str) -> Option<PushEncoding> {
+    match name {
+        "OP_PUSHDATA1" | "PUSHDATA1" => Some(PushEncoding::PushData1),
+        "OP_PUSHDATA2" | "PUSHDATA2" => Some(PushEncoding::PushData2),
+        "OP_PUSHDATA4" | "PUSHDATA4" => Some(PushEncoding::PushData4),
+        _ => None,
     }
+}
 
-    let mut script_vec: Vec<Script> = Vec::new();
+fn asm_error_message(kind: AsmParseErrorKind, word: &str) -> String {
+    match kind {
+        AsmParseErrorKind::UnknownToken if word.starts_with("OP_") => {
+            format!("unknown opcode `{}`", word)
+        }
+        AsmParseErrorKind::UnknownToken => {
+            format!("expected an opcode, a number or a push, found `{}`", word)
+        }
+        AsmParseErrorKind::NumberOutOfRange => {
+            format!("`{}` is out of the range of script numbers", word)
+        }
+        AsmParseErrorKind::InvalidHex => format!("`{}` isn't valid hex", word),
+        AsmParseErrorKind::UnprefixedHex => format!(
+            "hex needs `<{0}>` to be pushed or `0x{0}` for raw bytes, found `{0}`",
+            word
+        ),
+        AsmParseErrorKind::UnknownSighashType => format!("unknown sighash type in `{}`", word),
+        AsmParseErrorKind::TruncatedPushLength => {
+            format!("the push at `{}` ends in the middle of its size", word)
+        }
+        AsmParseErrorKind::TruncatedPushData => {
+            format!("the push at `{}` ends before all of its data", word)
+        }
+    }
+}
 
-    let mut input_iter = input.into_iter();
+// Words of Core's ParseScript syntax, parsed together since `0x` raw bytes may only make a
+// whole instruction with the words that follow. After an error, the words
+// left are parsed on their own for their errors to be reported too, unless a
+// truncated push took all of them.
+fn parse_words(words: &[(String, Span)], errors: &mut Vec<syn::Error>) -> Vec<u8> {
+    let mut asm = String::new();
+    let mut starts = Vec::new();
+    for (word, _) in words {
+        if !asm.is_empty() {
+            asm.push(' ');
+        }
+        starts.push(asm.len());
+        asm.push_str(word);
+    }
 
-    while let Some(token) = input_iter.next() {
-        match token {
-            TokenTree::Ident(ident) => {
-                let name = ident.to_string();
-
-                if name.starts_with("OP_") {
-                    match Script::from_str(&name) {
-                        Ok(op) => script_vec.push(op),
-                        Err(err) => panic_any(err),
-                    }
-                } else {
-                    script_vec.push(get_pushdata(&name, &mut input_iter));
+    match asm.from_parse_script() {
+        Ok(script) => script.from_script(),
+        Err(err) => {
+            let i = starts
+                .iter()
+                .rposition(|start| *start <= err.position)
+                .unwrap();
+            let (word, span) = &words[i];
+            errors.push(syn::Error::new(*span, asm_error_message(err.kind, word)));
+
+            match err.kind {
+                // the push took every word left
+                AsmParseErrorKind::TruncatedPushLength | AsmParseErrorKind::TruncatedPushData => {
+                    Vec::new()
                 }
+                _ => parse_words(&words[(i + 1)..], errors),
             }
-            TokenTree::Literal(literal) => {
-                let val = literal.to_string();
-                script_vec.push(get_pushdata(&val, &mut input_iter))
+        }
+    }
+}
+
+// Numbers are decimal like in ParseScript, a leading zero is most likely the
+// size of a push written in hex.
+fn number_word(word: String, span: Span, words: &mut Vec<(String, Span)>) -> Result<Option<Item>> {
+    let digits = word.trim_start_matches('-');
+    if digits.len() > 1 && digits.starts_with('0') && digits.bytes().all(|b| b.is_ascii_digit()) {
+        return error(
+            span,
+            format!(
+                "numbers are decimal, found `{}`: use `<hex>` or `0x…` for data",
+                word
+            ),
+        );
+    }
+
+    words.push((word, span));
+    Ok(None)
+}
+
+fn parse_token(
+    token: TokenTree,
+    iterator: &mut Peekable<impl Iterator<Item = TokenTree>>,
+    words: &mut Vec<(String, Span)>,
+) -> Result<Option<Item>> {
+    match &token {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+            if group.stream().is_empty() {
+                return error(token.span(), "expected an expression to push".to_string());
             }
-            _ => panic!("TokenTree variant not supported by script! {}", token),
+            Ok(Some(Item::Interpolated(group.stream())))
+        }
+        TokenTree::Punct(p) if p.as_char() == '<' => {
+            let data = get_push(&token, iterator)?;
+            Ok(Some(Item::Literal(push(&data))))
+        }
+        // a negative number
+        TokenTree::Punct(p) if p.as_char() == '-' => match iterator.next() {
+            Some(TokenTree::Literal(l)) => number_word(format!("-{}", l), l.span(), words),
+            _ => error(token.span(), "expected a number after `-`".to_string()),
+        },
+        TokenTree::Literal(l) => match syn::parse2::<syn::LitStr>(token.clone().into()) {
+            Ok(string) => Ok(Some(Item::Literal(push(string.value().as_bytes())))),
+            Err(_) => number_word(l.to_string(), l.span(), words),
+        },
+        TokenTree::Ident(ident) => {
+            let name = ident.to_string();
+
+            // `<data>` after an OP_PUSHDATAx pushes with it whatever the size,
+            // otherwise the size and data come next as raw bytes
+            match pushdata_encoding(&name) {
+                Some(encoding) if iterator.peek().is_some_and(|t| is_punct(t, '<')) => {
+                    let open = iterator.next().unwrap();
+                    let data = get_push(&open, iterator)?;
+                    match PushBytes::with_encoding(data, encoding) {
+                        Ok(push) => Ok(Some(Item::Literal([Script::Push(push)].from_script()))),
+                        Err(err) => error(open.span(), err.to_string()),
+                    }
+                }
+                _ => {
+                    words.push((name, ident.span()));
+                    Ok(None)
+                }
+            }
+        }
+        _ => error(
+            token.span(),
+            format!("expected an opcode or a push, found `{}`", token),
+        ),
+    }
+}
+
+// Literal parts next to each other are merged, for a script without `{expr}`
+// to be a single one.
+fn add_item(items: &mut Vec<Item>, item: Item) {
+    match (items.last_mut(), item) {
+        (Some(Item::Literal(script_hex)), Item::Literal(more)) => script_hex.extend(more),
+        (_, item) => items.push(item),
+    }
+}
+
+// Every problem is reported, each one on the token that caused it.
+fn parse(input: TokenStream) -> Result<Vec<Item>> {
+    if input.is_empty() {
+        return error(Span::call_site(), "expected a script".to_string());
+    }
+
+    let mut items: Vec<Item> = Vec::new();
+    let mut errors: Vec<syn::Error> = Vec::new();
+    let mut words: Vec<(String, Span)> = Vec::new();
+
+    let mut input_iter = input.into_iter().peekable();
+    while let Some(token) = input_iter.next() {
+        let item = parse_token(token, &mut input_iter, &mut words);
+        if let Ok(None) = item {
+            continue;
+        }
+
+        // the words before, in order for errors to be reported in order too
+        if !words.is_empty() {
+            add_item(&mut items, Item::Literal(parse_words(&words, &mut errors)));
+            words.clear();
+        }
+        match item {
+            Ok(Some(item)) => add_item(&mut items, item),
+            Ok(None) => {}
+            Err(err) => errors.push(err),
+        }
+    }
+    if !words.is_empty() {
+        add_item(&mut items, Item::Literal(parse_words(&words, &mut errors)));
+    }
+
+    match combine(errors) {
+        Some(errors) => Err(errors),
+        None => Ok(items),
+    }
+}
+
+// Literal parts of the script are serialized here, interpolated values are
+// pushed when the script is built.
+fn expand(items: Vec<Item>) -> TokenStream {
+    if let [Item::Literal(script_hex)] = items.as_slice() {
+        return quote! {
+            vec![#(#script_hex),*]
         };
     }
 
-    let script_hex = script_vec.from_script();
-    (quote! {
-        vec![#(#script_hex),*]
+    // not to be confused with a variable of the caller
+    let script = Ident::new("script", Span::mixed_site());
+    let parts = items.into_iter().map(|item| match item {
+        Item::Literal(script_hex) => quote!(#script.extend_from_slice(&[#(#script_hex),*]);),
+        Item::Interpolated(expr) => {
+            let span = expr.clone().into_iter().next().unwrap().span();
+            let push = quote_spanned!(span=> ::wallet::ToPush::to_push(&(#expr)));
+            quote!(#script.extend(::wallet::FromScript::from_script(&[#push][..]));)
+        }
+    });
+
+    quote!({
+        let mut #script: ::std::vec::Vec<u8> = ::std::vec::Vec::new();
+        #(#parts)*
+        #script
     })
-    .into()
+}
+
+// A block, for all of the errors to be reported, ending with a value of the
+// type the macro expands to.
+fn compile_errors(err: syn::Error, value: TokenStream) -> proc_macro::TokenStream {
+    let errors = err.to_compile_error();
+    quote!({ #errors #value }).into()
+}
+
+// Script bytes built with Core's test ASM, `<hex>` pushes and `{expr}` values,
+// like `script!(OP_DUP OP_HASH160 {hash} OP_EQUALVERIFY OP_CHECKSIG)`.
+#[proc_macro]
+pub fn script(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match parse(input.into()) {
+        Ok(items) => expand(items).into(),
+        Err(err) => compile_errors(err, quote!(::std::vec::Vec::<u8>::new())),
+    }
+}
+
+// Same as `script!`, as a `Vec<Script>`. Every part of the script is made of
+// whole instructions, the ASM parser makes sure of it for the literal ones.
+#[proc_macro]
+pub fn script_ops(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    match parse(input.into()) {
+        Ok(items) => {
+            let script_hex = expand(items);
+            quote!(::wallet::ToScript::to_script(&#script_hex[..])
+                .expect("script_ops! only builds whole instructions"))
+            .into()
+        }
+        Err(err) => compile_errors(err, quote!(::std::vec::Vec::<::wallet::Script>::new())),
+    }
+}
+
+// Same as `script!` without `{expr}` values, as a `&'static [u8; N]` that can
+// be used in consts and statics.
+#[proc_macro]
+pub fn const_script(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let items = parse(input.into()).and_then(|items| {
+        let errors = items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Interpolated(expr) => Some(syn::Error::new(
+                    expr.clone().into_iter().next().unwrap().span(),
+                    "const_script! can't push values known at runtime only",
+                )),
+                Item::Literal(_) => None,
+            })
+            .collect();
+
+        match combine(errors) {
+            Some(errors) => Err(errors),
+            None => Ok(items),
+        }
+    });
+
+    match items {
+        Ok(items) => {
+            let script_hex: Vec<u8> = items
+                .into_iter()
+                .flat_map(|item| match item {
+                    Item::Literal(script_hex) => script_hex,
+                    Item::Interpolated(_) => unreachable!("rejected above"),
+                })
+                .collect();
+
+            quote!(&[#(#script_hex),*]).into()
+        }
+        Err(err) => compile_errors(err, quote!(&[])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a script without `{expr}` is a single literal
+    fn literal(input: TokenStream) -> Vec<u8> {
+        match parse(input).unwrap().as_slice() {
+            [Item::Literal(bytes)] => bytes.clone(),
+            items => panic!("not a single literal: {:?}", items),
+        }
+    }
+
+    fn errors(input: TokenStream) -> Vec<String> {
+        parse(input)
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(errors(quote!()), vec!["expected a script"]);
+        assert_eq!(
+            errors(quote!(OP_DUP OP_FOO)),
+            vec!["unknown opcode `OP_FOO`"]
+        );
+        assert_eq!(
+            errors(quote!(OP_DUP, OP_DROP)),
+            vec!["expected an opcode or a push, found `,`"]
+        );
+        assert_eq!(
+            errors(quote!(DUP xy)),
+            vec!["expected an opcode, a number or a push, found `xy`"]
+        );
+        assert_eq!(
+            errors(quote!(42949672960)),
+            vec!["`42949672960` is out of the range of script numbers"]
+        );
+        assert_eq!(errors(quote!(0x4)), vec!["`0x4` isn't valid hex"]);
+        assert_eq!(
+            errors(quote!(0x4c)),
+            vec!["the push at `0x4c` ends in the middle of its size"]
+        );
+        assert_eq!(
+            errors(quote!(NOP 0x02 0x01)),
+            vec!["the push at `0x02` ends before all of its data"]
+        );
+        assert_eq!(
+            errors(quote!(DUP - DUP)),
+            vec!["expected a number after `-`"]
+        );
+        assert_eq!(
+            errors(quote!(OP_DUP {} OP_DROP)),
+            vec!["expected an expression to push"]
+        );
+        assert_eq!(
+            errors(quote!(OP_DUP < 0102)),
+            vec!["expected `>` to close this push"]
+        );
+        assert_eq!(
+            errors(quote!(<01 OP_DUP> OP_DROP)),
+            vec!["expected hex, found `OP_DUP`"]
+        );
+        assert_eq!(
+            errors(quote!(<abc>)),
+            vec!["`abc` has an odd number of hex digits"]
+        );
+        assert_eq!(errors(quote!(<()>)), vec!["expected hex, found `()`"]);
+        assert_eq!(errors(quote!(<"0x12">)), vec!["expected hex, found `0x12`"]);
+
+        // the size then hex of older versions doesn't build another script
+        assert_eq!(
+            errors(quote!(OP_HASH160 04 aaabbaab OP_EQUAL)),
+            vec![
+                "numbers are decimal, found `04`: use `<hex>` or `0x…` for data",
+                "hex needs `<aaabbaab>` to be pushed or `0xaaabbaab` for raw bytes, found `aaabbaab`"
+            ]
+        );
+        assert_eq!(
+            errors(quote!(OP_HASH160 14 aaabbaab OP_EQUAL)),
+            vec!["hex needs `<aaabbaab>` to be pushed or `0xaaabbaab` for raw bytes, found `aaabbaab`"]
+        );
+        assert_eq!(
+            errors(quote!(OP_RETURN 04 12345678)),
+            vec!["numbers are decimal, found `04`: use `<hex>` or `0x…` for data"]
+        );
+        assert_eq!(
+            errors(quote!(-01)),
+            vec!["numbers are decimal, found `-01`: use `<hex>` or `0x…` for data"]
+        );
+        assert_eq!(literal(quote!(0 -0 10)), hex::decode("00005a").unwrap());
+
+        // every problem is reported
+        assert_eq!(
+            errors(quote!(OP_FOO OP_DUP OP_BAR ; 1 {} 0x4)),
+            vec![
+                "unknown opcode `OP_FOO`",
+                "unknown opcode `OP_BAR`",
+                "expected an opcode or a push, found `;`",
+                "expected an expression to push",
+                "`0x4` isn't valid hex"
+            ]
+        );
+    }
+
+    #[test]
+    fn literals_are_merged() {
+        assert_eq!(literal(quote!(OP_DUP<01>)), vec![0x76, 0x51]);
+        assert_eq!(literal(quote!("a" OP_SIZE)), vec![0x01, 0x61, 0x82]);
+        assert_eq!(
+            expand(parse(quote!(OP_DUP <01> "a" OP_PUSHDATA1 <> 2)).unwrap()).to_string(),
+            quote!(vec![118u8, 81u8, 1u8, 97u8, 76u8, 0u8, 82u8]).to_string()
+        );
+
+        let items = parse(quote!(OP_DUP <01> {x} "a" OP_SIZE)).unwrap();
+        assert!(matches!(
+            items.as_slice(),
+            [Item::Literal(_), Item::Interpolated(_), Item::Literal(_)]
+        ));
+    }
+
+    #[test]
+    fn parse_script() {
+        assert_eq!(
+            literal(quote!(OP_DUP DUP 0 1 -1 16 17 -2 1000 NOP2 2DUP)),
+            vec![
+                0x76, 0x76, 0x00, 0x51, 0x4f, 0x60, 0x01, 0x11, 0x01, 0x82, 0x02, 0xe8, 0x03, 0xb1,
+                0x6e
+            ]
+        );
+        assert_eq!(
+            literal(quote!(0x4c 0x01 0x07 OP_PUSHDATA2 0x0100 0x08 "" "a" "Az")),
+            vec![0x4c, 0x01, 0x07, 0x4d, 0x01, 0x00, 0x08, 0x00, 0x01, 0x61, 0x02, 0x41, 0x7a]
+        );
+        assert_eq!(
+            literal(quote!(<> <07> <0102 03> OP_PUSHDATA1 <> OP_PUSHDATA4 <07>)),
+            vec![
+                0x00, 0x57, 0x03, 0x01, 0x02, 0x03, 0x4c, 0x00, 0x4e, 0x01, 0x00, 0x00, 0x00, 0x07
+            ]
+        );
+    }
 }
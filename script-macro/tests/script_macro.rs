@@ -1,4 +1,5 @@
-use script_macro::script;
+use script_macro::{const_script, script, script_ops};
+use wallet::{FromAsm, FromScript, Script};
 
 #[test]
 fn hex_is_correct() {
@@ -7,7 +8,7 @@ fn hex_is_correct() {
         0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0x88, 0xAC,
     ];
     let script = script!(
-        OP_DUP OP_HASH160 14 aaabcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG
+        OP_DUP OP_HASH160 0x14 0xaaabcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG
     );
     assert_eq!(script, hex_script);
 
@@ -16,25 +17,174 @@ fn hex_is_correct() {
         0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0x88, 0xAC,
     ];
     let script = script!(
-        OP_DUP OP_HASH160 14 89abcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG
+        OP_DUP OP_HASH160 0x14 0x89abcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG
     );
     assert_eq!(script, hex_script);
 
     let hex_script: Vec<u8> = vec![0x4c, 0x01, 0x07];
     let script = script!(
-        OP_PUSHDATA1 01 07
+        OP_PUSHDATA1 0x01 0x07
     );
     assert_eq!(script, hex_script);
 
     let hex_script: Vec<u8> = vec![0x4d, 0x01, 0x00, 0x08];
     let script = script!(
-        OP_PUSHDATA2 0100 08
+        OP_PUSHDATA2 0x0100 0x08
     );
     assert_eq!(script, hex_script);
 
     let hex_script: Vec<u8> = vec![0x4e, 0x01, 0x00, 0x00, 0x00, 0x09];
     let script = script!(
-        OP_PUSHDATA4 01000000 09
+        OP_PUSHDATA4 0x01000000 0x09
     );
     assert_eq!(script, hex_script);
 }
+
+#[test]
+fn push_sizes_are_inferred() {
+    let hex_script: Vec<u8> = vec![
+        0x76, 0xA9, 0x14, 0x89, 0xAB, 0xCD, 0xEF, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA,
+        0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0x88, 0xAC,
+    ];
+    let script = script!(
+        OP_DUP OP_HASH160 <89abcdefabbaabbaabbaabbaabbaabbaabbaabba> OP_EQUALVERIFY OP_CHECKSIG
+    );
+    assert_eq!(script, hex_script);
+
+    // hex Rust can't lex as it is, here `021e` as an exponent, goes in a
+    // string or after `0x`
+    let hex_script: Vec<u8> = [
+        vec![0xa9, 0x14],
+        hex::decode("021e6b6cb6ab2e8a6b1f34d0b97ec0b6b2a1b3f7").unwrap(),
+        vec![0x87],
+    ]
+    .concat();
+    assert_eq!(
+        script!(OP_HASH160 <"021e6b6cb6ab2e8a6b1f34d0b97ec0b6b2a1b3f7"> OP_EQUAL),
+        hex_script
+    );
+    assert_eq!(
+        script!(OP_HASH160 <0x021e6b6cb6ab2e8a6b1f34d0b97ec0b6b2a1b3f7> OP_EQUAL),
+        hex_script
+    );
+    assert_eq!(script!(<0x0b12 "021e">), vec![0x04, 0x0b, 0x12, 0x02, 0x1e]);
+
+    // 76 bytes don't fit in a direct push
+    let script = script!(
+        <
+            89abcdefabbaabbaabbaabbaabbaabbaabbaabba 89abcdefabbaabbaabbaabbaabbaabbaabbaabba
+            89abcdefabbaabbaabbaabbaabbaabbaabbaabba 89abcdefabbaabbaabbaabbaabbaabba
+        >
+    );
+    assert_eq!(script[..2], [0x4c, 0x4c]);
+    assert_eq!(script.len(), 2 + 76);
+
+    // the push MINIMALDATA accepts, like for `{expr}` values
+    assert_eq!(
+        script!(<> <01> <10> <11> <81>),
+        vec![0x00, 0x51, 0x60, 0x01, 0x11, 0x4f]
+    );
+    assert_eq!(script!(<01>), script!({ [1u8] }));
+
+    // non-minimal pushes are kept as written
+    let hex_script: Vec<u8> = vec![
+        0x4c, 0x01, 0x07, 0x4d, 0x01, 0x00, 0x08, 0x4e, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let script = script!(
+        OP_PUSHDATA1 <07> OP_PUSHDATA2 <08> OP_PUSHDATA4 <>
+    );
+    assert_eq!(script, hex_script);
+}
+
+#[test]
+fn interpolation() {
+    let hash = [0x89; 20];
+    let script = script!(OP_DUP OP_HASH160 {hash} OP_EQUALVERIFY OP_CHECKSIG);
+    assert_eq!(script, Script::p2pkh(&hash).from_script());
+
+    // numbers are pushed as script numbers, bytes as they are
+    let locktime: u32 = 500_000;
+    let pubkey = vec![0x02; 33];
+    let script = script!({locktime} OP_CHECKLOCKTIMEVERIFY OP_DROP {&pubkey} OP_CHECKSIG);
+    assert_eq!(
+        script,
+        [
+            vec![0x03, 0x20, 0xa1, 0x07, 0xb1, 0x75, 0x21],
+            pubkey.clone(),
+            vec![0xac]
+        ]
+        .concat()
+    );
+
+    let script = script!({1 + 1} {-1} {"abc"} {&pubkey[..0]});
+    assert_eq!(script, vec![0x52, 0x4f, 0x03, 0x61, 0x62, 0x63, 0x00]);
+
+    // the variable the expansion builds the script in doesn't hide the caller's
+    let script = [0x07, 0x08];
+    assert_eq!(script!({ script }), vec![0x02, 0x07, 0x08]);
+}
+
+// Scripts from Core's script_tests.json, written the same way
+#[test]
+fn core_asm() {
+    let cases = [
+        (script!(1 2 ADD 3 EQUAL), "1 2 ADD 3 EQUAL"),
+        (script!(-1 0 1000 0x7fffffff), "-1 0 1000 0x7fffffff"),
+        (
+            script!(0x4c 0x01 0x07 NOP2 OP_CHECKSEQUENCEVERIFY),
+            "0x4c 0x01 0x07 NOP2 OP_CHECKSEQUENCEVERIFY",
+        ),
+        (
+            script!(2147483648 -2147483648 4294967295),
+            "2147483648 -2147483648 4294967295",
+        ),
+        (script!(0x02 0x0100 NOT 2DUP), "0x02 0x0100 NOT 2DUP"),
+    ];
+
+    for (script, asm) in cases.iter() {
+        assert_eq!(
+            *script,
+            asm.from_parse_script().unwrap().from_script(),
+            "{}",
+            asm
+        );
+    }
+
+    // strings are Rust literals instead of between single quotes
+    assert_eq!(
+        script!("" "a" "Az" SIZE),
+        "'' 'a' 'Az' SIZE"
+            .from_parse_script()
+            .unwrap()
+            .from_script()
+    );
+}
+
+#[test]
+fn script_ops() {
+    let hash = [0x89; 20];
+    assert_eq!(
+        script_ops!(OP_DUP OP_HASH160 {hash} OP_EQUALVERIFY OP_CHECKSIG),
+        Script::p2pkh(&hash)
+    );
+    assert_eq!(
+        script_ops!(1 <07> OP_PUSHDATA1 <07> 0x4c 0x01 0x07 "a"),
+        "1 7 0x4c 0x01 0x07 0x4c 0x01 0x07 'a'"
+            .from_parse_script()
+            .unwrap()
+    );
+}
+
+const P2PKH_PREFIX: &[u8] = const_script!(OP_DUP OP_HASH160);
+static ANYONE_CAN_SPEND: &[u8] = const_script!(1);
+
+#[test]
+fn const_script() {
+    assert_eq!(P2PKH_PREFIX, [0x76, 0xa9]);
+    assert_eq!(ANYONE_CAN_SPEND, [0x51]);
+
+    let p2pkh: [u8; 25] = *const_script!(
+        OP_DUP OP_HASH160 <8989898989898989898989898989898989898989> OP_EQUALVERIFY OP_CHECKSIG
+    );
+    assert_eq!(p2pkh.to_vec(), Script::p2pkh(&[0x89; 20]).from_script());
+}
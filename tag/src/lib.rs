@@ -2,6 +2,11 @@ pub trait ToTag {
     fn to_tag(&self) -> u8;
 }
 
-pub trait FromTag: ToTag {
-    fn from_tag(tag: u8) -> Self;
+pub trait FromTag: ToTag + Sized {
+    // Returns `None` for tags that don't belong to any variant.
+    fn try_from_tag(tag: u8) -> Option<Self>;
+
+    fn from_tag(tag: u8) -> Self {
+        Self::try_from_tag(tag).expect("Some variant does not have a explicit discriminant.")
+    }
 }
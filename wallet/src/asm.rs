@@ -0,0 +1,632 @@
+use crate::interpreter::{
+    check_signature_encoding, serialize_push, ScriptNum, VerifyFlags, MAX_SCRIPT_SIZE,
+};
+use crate::script::{FromScript, Script, ScriptParseErrorKind, ToScript};
+use std::str::FromStr;
+use std::{error, fmt};
+
+// Same names as Core's mapSigHashTypes
+fn sighash_name(hash_type: u8) -> Option<&'static str> {
+    match hash_type {
+        0x01 => Some("ALL"),
+        0x02 => Some("NONE"),
+        0x03 => Some("SINGLE"),
+        0x81 => Some("ALL|ANYONECANPAY"),
+        0x82 => Some("NONE|ANYONECANPAY"),
+        0x83 => Some("SINGLE|ANYONECANPAY"),
+        _ => None,
+    }
+}
+
+// Pushes of up to 4 bytes are shown as the number they encode, longer ones as
+// hex. With `decode_sighash`, anything that is a valid signature under
+// STRICTENC gets its sighash byte replaced by the name of the type, like
+// `3044...[ALL]`.
+fn write_push(f: &mut fmt::Formatter, data: &[u8], decode_sighash: bool) -> fmt::Result {
+    if data.len() <= ScriptNum::DEFAULT_MAX_SIZE {
+        let number = ScriptNum::from_bytes(data, false, ScriptNum::DEFAULT_MAX_SIZE).unwrap();
+        return write!(f, "{}", number.0);
+    }
+
+    if decode_sighash && check_signature_encoding(data, VerifyFlags::STRICTENC).is_ok() {
+        let (hash_type, signature) = data.split_last().unwrap();
+        if let Some(name) = sighash_name(*hash_type) {
+            return write!(f, "{}[{}]", hex::encode(signature), name);
+        }
+    }
+
+    write!(f, "{}", hex::encode(data))
+}
+
+// Formats an opcode the way Core's GetOpName does, and a push like
+// ScriptToAsmStr does.
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Script::Push(push) => write_push(f, push.data(), false),
+            // the template placeholders aren't opcodes in Core
+            Script::OP_UNKNOWN(_) | Script::OP_PUBKEYHASH | Script::OP_PUBKEY => {
+                write!(f, "OP_UNKNOWN")
+            }
+            Script::OP_FALSE => write!(f, "0"),
+            Script::OP_1NEGATE => write!(f, "-1"),
+            _ => match self.to_opcode() {
+                opcode @ 0x51..=0x60 => write!(f, "{}", opcode - 0x50),
+                _ => write!(f, "{:?}", self),
+            },
+        }
+    }
+}
+
+// A script formatted like Core's ScriptToAsmStr, see `ToAsm`.
+pub struct Asm<'a> {
+    script: &'a [Script],
+    attempt_sighash_decode: bool,
+}
+
+impl fmt::Display for Asm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // OP_RETURN data may look like a signature, it is never decoded
+        let unspendable = matches!(self.script.first(), Some(Script::OP_RETURN))
+            || self.script.from_script().len() > MAX_SCRIPT_SIZE;
+        let decode_sighash = self.attempt_sighash_decode && !unspendable;
+
+        for (i, op) in self.script.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            match op {
+                Script::Push(push) => write_push(f, push.data(), decode_sighash)?,
+                _ => write!(f, "{}", op)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub trait ToAsm {
+    // `attempt_sighash_decode` should only be set for scriptSigs, where
+    // pushes that look like signatures are most likely signatures
+    fn to_asm(&self, attempt_sighash_decode: bool) -> Asm<'_>;
+}
+
+impl ToAsm for [Script] {
+    fn to_asm(&self, attempt_sighash_decode: bool) -> Asm<'_> {
+        Asm {
+            script: self,
+            attempt_sighash_decode,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AsmParseErrorKind {
+    // not a number, raw bytes, a string, an opcode or a hex push
+    UnknownToken,
+    // decimal numbers must be within -0xffffffff..=0xffffffff, like in Core
+    NumberOutOfRange,
+    // `0x` followed by an odd number of digits or something else than hex
+    InvalidHex,
+    // hex data without `0x`, which ParseScript syntax doesn't have
+    UnprefixedHex,
+    // the name between brackets after a signature is not a sighash type
+    UnknownSighashType,
+    // raw bytes end in the middle of the size of an OP_PUSHDATAx
+    TruncatedPushLength,
+    // raw bytes end before all the bytes a push announced
+    TruncatedPushData,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AsmParseError {
+    // byte offset, in the ASM, of the word that could not be parsed
+    pub position: usize,
+    pub kind: AsmParseErrorKind,
+}
+
+impl fmt::Display for AsmParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            AsmParseErrorKind::UnknownToken => "unknown token",
+            AsmParseErrorKind::NumberOutOfRange => "number out of range",
+            AsmParseErrorKind::InvalidHex => "invalid hex",
+            AsmParseErrorKind::UnprefixedHex => "hex without 0x",
+            AsmParseErrorKind::UnknownSighashType => "unknown sighash type",
+            AsmParseErrorKind::TruncatedPushLength => "truncated push length",
+            AsmParseErrorKind::TruncatedPushData => "truncated push data",
+        };
+        write!(f, "{} at position {}", reason, self.position)
+    }
+}
+
+impl error::Error for AsmParseError {}
+
+fn sighash_type(name: &str) -> Option<u8> {
+    [0x01, 0x02, 0x03, 0x81, 0x82, 0x83]
+        .into_iter()
+        .find(|hash_type| sighash_name(*hash_type) == Some(name))
+}
+
+// Core's names that are not variants of `Script`
+fn opcode_alias(name: &str) -> Option<Script> {
+    match name {
+        "OP_0" => Some(Script::OP_FALSE),
+        "OP_1" => Some(Script::OP_TRUE),
+        "OP_NOP2" => Some(Script::OP_CHECKLOCKTIMEVERIFY),
+        "OP_NOP3" => Some(Script::OP_CHECKSEQUENCEVERIFY),
+        _ => None,
+    }
+}
+
+fn parse_opcode(word: &str) -> Option<Script> {
+    let name = if word.starts_with("OP_") {
+        word.to_string()
+    } else {
+        format!("OP_{}", word)
+    };
+
+    Script::from_str(&name).ok().or_else(|| opcode_alias(&name))
+}
+
+// Pushes aren't opcodes of their own, but in ParseScript syntax their size
+// and data can follow as raw bytes
+fn parse_push_opcode(word: &str) -> Option<u8> {
+    match word.strip_prefix("OP_").unwrap_or(word) {
+        "PUSHDATA1" => Some(0x4c),
+        "PUSHDATA2" => Some(0x4d),
+        "PUSHDATA4" => Some(0x4e),
+        _ => None,
+    }
+}
+
+fn parse_hex(hex_str: &str) -> Option<Vec<u8>> {
+    if hex_str.is_empty() {
+        return None;
+    }
+    hex::decode(hex_str).ok()
+}
+
+fn is_decimal(word: &str) -> bool {
+    let digits = word.strip_prefix('-').unwrap_or(word);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+// Pushes a decimal number the shortest way: OP_0, OP_1NEGATE, OP_1 to OP_16
+// or a script number.
+fn push_number(word: &str, script: &mut Vec<u8>) -> Result<(), AsmParseErrorKind> {
+    let number = word
+        .parse::<i64>()
+        .ok()
+        .filter(|n| n.abs() <= 0xffffffff)
+        .ok_or(AsmParseErrorKind::NumberOutOfRange)?;
+
+    match number {
+        0 => script.push(Script::OP_FALSE.to_opcode()),
+        n @ (-1 | 1..=16) => script.push((Script::OP_TRUE.to_opcode() as i64 + n - 1) as u8),
+        n => script.extend(serialize_push(&ScriptNum(n).to_bytes())),
+    }
+
+    Ok(())
+}
+
+// Appends the bytes of a word of ScriptToAsmStr output to `script`.
+fn parse_asm_word(word: &str, script: &mut Vec<u8>) -> Result<(), AsmParseErrorKind> {
+    // numbers come from pushes of up to 4 bytes, so they have at most 10
+    // digits, while longer pushes are printed as at least 10 hex digits
+    if is_decimal(word) && (word.starts_with('-') || word.len() < 10) {
+        push_number(word, script)?;
+    } else if let Some(op) = parse_opcode(word) {
+        script.push(op.to_opcode());
+    } else if let Some((signature, name)) =
+        word.strip_suffix(']').and_then(|word| word.split_once('['))
+    {
+        let mut data = parse_hex(signature).ok_or(AsmParseErrorKind::InvalidHex)?;
+        data.push(sighash_type(name).ok_or(AsmParseErrorKind::UnknownSighashType)?);
+        script.extend(serialize_push(&data));
+    } else if is_decimal(word) {
+        let data = parse_hex(word).ok_or(AsmParseErrorKind::InvalidHex)?;
+        script.extend(serialize_push(&data));
+    } else {
+        let data = parse_hex(word).ok_or(AsmParseErrorKind::UnknownToken)?;
+        script.extend(serialize_push(&data));
+    }
+
+    Ok(())
+}
+
+// Appends the bytes of a word of ParseScript syntax to `script`.
+fn parse_script_word(word: &str, script: &mut Vec<u8>) -> Result<(), AsmParseErrorKind> {
+    if is_decimal(word) {
+        push_number(word, script)?;
+    } else if let Some(raw) = word.strip_prefix("0x") {
+        script.extend(parse_hex(raw).ok_or(AsmParseErrorKind::InvalidHex)?);
+    } else if word.len() >= 2 && word.starts_with('\'') && word.ends_with('\'') {
+        script.extend(serialize_push(&word.as_bytes()[1..word.len() - 1]));
+    } else if let Some(opcode) = parse_push_opcode(word) {
+        script.push(opcode);
+    } else if let Some(op) = parse_opcode(word) {
+        script.push(op.to_opcode());
+    } else if parse_hex(word).is_some() {
+        return Err(AsmParseErrorKind::UnprefixedHex);
+    } else {
+        return Err(AsmParseErrorKind::UnknownToken);
+    }
+
+    Ok(())
+}
+
+fn parse_words(
+    asm: &str,
+    parse_word: fn(&str, &mut Vec<u8>) -> Result<(), AsmParseErrorKind>,
+) -> Result<Vec<Script>, AsmParseError> {
+    let mut script = Vec::new();
+    // where each word starts, in `script` and in `asm`
+    let mut starts = Vec::new();
+
+    for word in asm.split_whitespace() {
+        let position = word.as_ptr() as usize - asm.as_ptr() as usize;
+        starts.push((script.len(), position));
+
+        parse_word(word, &mut script).map_err(|kind| AsmParseError { position, kind })?;
+    }
+
+    script.to_script().map_err(|err| {
+        let position = starts
+            .iter()
+            .rev()
+            .find(|(offset, _)| *offset <= err.offset)
+            .map_or(0, |(_, position)| *position);
+        let kind = match err.kind {
+            ScriptParseErrorKind::TruncatedPushLength => AsmParseErrorKind::TruncatedPushLength,
+            ScriptParseErrorKind::TruncatedPushData => AsmParseErrorKind::TruncatedPushData,
+        };
+
+        AsmParseError { position, kind }
+    })
+}
+
+pub trait FromAsm {
+    // Parses what `ToAsm` prints, like Core's ScriptToAsmStr. Words are
+    // separated by whitespace and can be:
+    // - a decimal number, pushed the shortest way (OP_0, OP_1NEGATE, OP_1 to
+    //   OP_16 or a script number)
+    // - an opcode, with or without its `OP_` prefix
+    // - hex, pushed as is. A sighash type in brackets, like `3044...[ALL]`,
+    //   is pushed as its byte
+    // Words of 10 digits or more are hex: Core prints every push of 5 bytes
+    // or more that way. Only 4-byte pushes of numbers from 1000000000 up
+    // can't be told apart from them, those come back as hex.
+    fn from_asm(&self) -> Result<Vec<Script>, AsmParseError>;
+
+    // Parses Core's ParseScript syntax, the one of script_tests.json. Words
+    // are separated by whitespace and can be:
+    // - a decimal number, pushed the shortest way. Digits are always decimal,
+    //   `0000000001` is 1
+    // - `0x` followed by hex, inserted as raw bytes. These can span several
+    //   words, like `0x4c 0x01 0x07`
+    // - a string between single quotes (without spaces), pushed as is
+    // - an opcode, with or without its `OP_` prefix. OP_PUSHDATAx is inserted
+    //   alone, its size and data have to follow as raw bytes
+    // There is no bare hex, it fails with `UnprefixedHex`.
+    fn from_parse_script(&self) -> Result<Vec<Script>, AsmParseError>;
+}
+
+impl FromAsm for str {
+    fn from_asm(&self) -> Result<Vec<Script>, AsmParseError> {
+        parse_words(self, parse_asm_word)
+    }
+
+    fn from_parse_script(&self) -> Result<Vec<Script>, AsmParseError> {
+        parse_words(self, parse_script_word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Test cases from the following source:
+    // - https://github.com/bitcoin/bitcoin/blob/7fcf53f7b4524572d1d0c9a5fdc388e87eb02416/src/test/script_tests.cpp (script_GetScriptAsm)
+
+    use super::*;
+    use crate::script::{PushBytes, ToScript};
+
+    const DER_SIG: &str = "304502207fa7a6d1e0ee81132a269ad84e68d695483745cde8b541e3bf630749894e342a022100c1f7ab20e13e22fb95281a870f3dcf38d782e53023ee313d741ad0cfbc0c5090";
+    const PUBKEY: &str = "03b0da749730dc9b4b1f4a14d6902877a92541f5368778853d9c4a0cb7802dcfb2";
+
+    fn asm(hex_script: &str, attempt_sighash_decode: bool) -> String {
+        let script = hex::decode(hex_script).unwrap().to_script().unwrap();
+        script.to_asm(attempt_sighash_decode).to_string()
+    }
+
+    fn sig_and_pubkey(hash_type: &str) -> String {
+        format!("48{}{}21{}", DER_SIG, hash_type, PUBKEY)
+    }
+
+    #[test]
+    fn opcodes() {
+        assert_eq!(Script::OP_DUP.to_string(), "OP_DUP");
+        assert_eq!(
+            Script::OP_CHECKLOCKTIMEVERIFY.to_string(),
+            "OP_CHECKLOCKTIMEVERIFY"
+        );
+        assert_eq!(Script::OP_FALSE.to_string(), "0");
+        assert_eq!(Script::OP_1NEGATE.to_string(), "-1");
+        assert_eq!(Script::OP_TRUE.to_string(), "1");
+        assert_eq!(Script::OP_16.to_string(), "16");
+        assert_eq!(Script::OP_UNKNOWN(0xfc).to_string(), "OP_UNKNOWN");
+        assert_eq!(Script::OP_PUBKEYHASH.to_string(), "OP_UNKNOWN");
+        assert_eq!(Script::OP_PUBKEY.to_string(), "OP_UNKNOWN");
+        assert_eq!(Script::OP_INVALIDOPCODE.to_string(), "OP_INVALIDOPCODE");
+        assert_eq!(
+            Script::Push(PushBytes::new(vec![0x0b]).unwrap()).to_string(),
+            "11"
+        );
+        assert_eq!(
+            Script::Push(PushBytes::new(vec![0x01, 0x02, 0x03, 0x04, 0x05]).unwrap()).to_string(),
+            "0102030405"
+        );
+    }
+
+    #[test]
+    fn pushes() {
+        assert_eq!(
+            asm("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac", false),
+            "OP_DUP OP_HASH160 89abcdefabbaabbaabbaabbaabbaabbaabbaabba OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        // numbers up to 4 bytes, whatever the push that holds them
+        assert_eq!(asm("0100", false), "0");
+        assert_eq!(asm("0181", false), "-1");
+        assert_eq!(asm("02ff00", false), "255");
+        assert_eq!(asm("04ffffffff", false), "-2147483647");
+        assert_eq!(asm("4c0107", false), "7");
+        assert_eq!(asm("4d0100084e00000000", false), "8 0");
+        assert_eq!(asm("4c050102030405", false), "0102030405");
+        assert_eq!(asm("004f515f60", false), "0 -1 1 15 16");
+        assert_eq!(asm("", false), "");
+        assert_eq!(
+            asm("b1b2bafc", false),
+            "OP_CHECKLOCKTIMEVERIFY OP_CHECKSEQUENCEVERIFY OP_CHECKSIGADD OP_UNKNOWN"
+        );
+    }
+
+    #[test]
+    fn sighash_decode() {
+        for (hash_type, name) in [
+            ("01", "ALL"),
+            ("02", "NONE"),
+            ("03", "SINGLE"),
+            ("81", "ALL|ANYONECANPAY"),
+            ("82", "NONE|ANYONECANPAY"),
+            ("83", "SINGLE|ANYONECANPAY"),
+        ] {
+            assert_eq!(
+                asm(&sig_and_pubkey(hash_type), true),
+                format!("{}[{}] {}", DER_SIG, name, PUBKEY)
+            );
+            assert_eq!(
+                asm(&sig_and_pubkey(hash_type), false),
+                format!("{}{} {}", DER_SIG, hash_type, PUBKEY)
+            );
+        }
+
+        // undefined types are left alone
+        for hash_type in ["00", "80"] {
+            assert_eq!(
+                asm(&sig_and_pubkey(hash_type), true),
+                format!("{}{} {}", DER_SIG, hash_type, PUBKEY)
+            );
+        }
+
+        // and so is anything after an OP_RETURN
+        assert_eq!(
+            asm(&format!("6a48{}01", DER_SIG), true),
+            format!("OP_RETURN {}01", DER_SIG)
+        );
+    }
+
+    fn from_asm(asm: &str) -> Vec<u8> {
+        asm.from_asm().unwrap().from_script()
+    }
+
+    fn parse_script(asm: &str) -> Vec<u8> {
+        asm.from_parse_script().unwrap().from_script()
+    }
+
+    fn asm_error(asm: &str) -> (usize, AsmParseErrorKind) {
+        let err = asm.from_asm().unwrap_err();
+        (err.position, err.kind)
+    }
+
+    fn parse_script_error(asm: &str) -> (usize, AsmParseErrorKind) {
+        let err = asm.from_parse_script().unwrap_err();
+        (err.position, err.kind)
+    }
+
+    #[test]
+    fn parse_script_syntax() {
+        assert_eq!(parse_script(""), Vec::<u8>::new());
+        assert_eq!(parse_script("  \t\n "), Vec::<u8>::new());
+        assert_eq!(
+            parse_script("0 -1 1 16 17 -2 1000"),
+            hex::decode("004f51600111018202e803").unwrap()
+        );
+        assert_eq!(
+            parse_script("4294967295"),
+            hex::decode("05ffffffff00").unwrap()
+        );
+        assert_eq!(
+            parse_script("-4294967295"),
+            hex::decode("05ffffffff80").unwrap()
+        );
+        assert_eq!(
+            parse_script("0x4c 0x01 0x07"),
+            hex::decode("4c0107").unwrap()
+        );
+        assert_eq!(
+            parse_script("0x4c01 0x07 NOP"),
+            hex::decode("4c010761").unwrap()
+        );
+        assert_eq!(
+            parse_script("PUSHDATA2 0x0100 0x08 OP_PUSHDATA4 0x00000000"),
+            hex::decode("4d0100084e00000000").unwrap()
+        );
+        assert_eq!(
+            parse_script("'' 'a' 'Az'"),
+            hex::decode("00016102417a").unwrap()
+        );
+        assert_eq!(
+            parse_script(&format!("'{}'", "a".repeat(76))),
+            [hex::decode("4c4c").unwrap(), vec![b'a'; 76]].concat()
+        );
+        assert_eq!(
+            parse_script("DUP OP_DUP 2DUP OP_2DUP 1ADD"),
+            hex::decode("76766e6e8b").unwrap()
+        );
+        assert_eq!(
+            parse_script("NOP2 OP_NOP3 CHECKLOCKTIMEVERIFY"),
+            hex::decode("b1b2b1").unwrap()
+        );
+        assert_eq!(
+            parse_script("OP_0 OP_1 OP_FALSE OP_TRUE OP_16"),
+            hex::decode("0051005160").unwrap()
+        );
+        assert_eq!(
+            parse_script("RESERVED CHECKSIGADD"),
+            hex::decode("50ba").unwrap()
+        );
+        // digits are always a number, however long
+        assert_eq!(parse_script("0000000001"), hex::decode("51").unwrap());
+        assert_eq!(
+            parse_script("1234567890"),
+            hex::decode("04d2029649").unwrap()
+        );
+    }
+
+    #[test]
+    fn core_asm() {
+        // ASM doesn't tell how data was pushed, only minimal pushes round trip
+        for hex_script in [
+            "76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac",
+            "a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba87",
+            "00140102030405060708090a0b0c0d0e0f1011121314",
+            "004f515f60",
+            "0500000000004f",
+            "050102030405",
+            "051234567890",
+            "0a12345678901234567890",
+            "6a0b68656c6c6f20776f726c64",
+            &sig_and_pubkey("01"),
+            &sig_and_pubkey("83"),
+            &sig_and_pubkey("00"),
+        ] {
+            let script = hex::decode(hex_script).unwrap().to_script().unwrap();
+
+            for attempt_sighash_decode in [false, true] {
+                let asm = script.to_asm(attempt_sighash_decode).to_string();
+                assert_eq!(
+                    asm.from_asm().unwrap().from_script(),
+                    script.from_script(),
+                    "{}",
+                    asm
+                );
+            }
+        }
+
+        // what Core prints as a number is a minimal push
+        assert_eq!(
+            from_asm("1 -1 255"),
+            hex::decode("51 4f 02ff00".replace(' ', "")).unwrap()
+        );
+        assert_eq!(from_asm("deadbeef"), hex::decode("04deadbeef").unwrap());
+        assert_eq!(from_asm("0102030405"), hex::decode("050102030405").unwrap());
+        assert_eq!(from_asm("1234567890"), hex::decode("051234567890").unwrap());
+        assert_eq!(from_asm("-1234567890"), hex::decode("04d20296c9").unwrap());
+        assert_eq!(from_asm("010203[ALL]"), hex::decode("0401020301").unwrap());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            parse_script_error("1 ADD FOO"),
+            (6, AsmParseErrorKind::UnknownToken)
+        );
+        assert_eq!(
+            parse_script_error("OP_UNKNOWN"),
+            (0, AsmParseErrorKind::UnknownToken)
+        );
+        assert_eq!(
+            parse_script_error("OP_ abc 'a"),
+            (0, AsmParseErrorKind::UnknownToken)
+        );
+        // no hex without `0x`, nor decoded sighash types
+        assert_eq!(
+            parse_script_error("1 abcd"),
+            (2, AsmParseErrorKind::UnprefixedHex)
+        );
+        assert_eq!(
+            parse_script_error("14 89abcdefabbaabbaabbaabbaabbaabbaabbaabba"),
+            (3, AsmParseErrorKind::UnprefixedHex)
+        );
+        assert_eq!(
+            parse_script_error("1 abc"),
+            (2, AsmParseErrorKind::UnknownToken)
+        );
+        assert_eq!(
+            parse_script_error("010203[ALL]"),
+            (0, AsmParseErrorKind::UnknownToken)
+        );
+        assert_eq!(
+            parse_script_error("NOP 42949672960"),
+            (4, AsmParseErrorKind::NumberOutOfRange)
+        );
+        assert_eq!(
+            parse_script_error("NOP -4294967296"),
+            (4, AsmParseErrorKind::NumberOutOfRange)
+        );
+        assert_eq!(parse_script_error("0x"), (0, AsmParseErrorKind::InvalidHex));
+        assert_eq!(
+            parse_script_error("0x4"),
+            (0, AsmParseErrorKind::InvalidHex)
+        );
+        assert_eq!(
+            parse_script_error("1 0xzz"),
+            (2, AsmParseErrorKind::InvalidHex)
+        );
+        assert_eq!(
+            parse_script_error("NOP 0x4d01"),
+            (4, AsmParseErrorKind::TruncatedPushLength)
+        );
+        assert_eq!(
+            parse_script_error("NOP 0x4c 0x02 0x01"),
+            (4, AsmParseErrorKind::TruncatedPushData)
+        );
+        assert_eq!(
+            parse_script_error("NOP 0x02 0x01"),
+            (4, AsmParseErrorKind::TruncatedPushData)
+        );
+        assert_eq!(
+            "1 ADD FOO".from_parse_script().unwrap_err().to_string(),
+            "unknown token at position 6"
+        );
+
+        assert_eq!(asm_error("1 ADD FOO"), (6, AsmParseErrorKind::UnknownToken));
+        assert_eq!(asm_error("1 abc"), (2, AsmParseErrorKind::UnknownToken));
+        // ParseScript syntax isn't ASM
+        assert_eq!(asm_error("0x01 0x07"), (0, AsmParseErrorKind::UnknownToken));
+        assert_eq!(asm_error("'a'"), (0, AsmParseErrorKind::UnknownToken));
+        assert_eq!(
+            asm_error("OP_PUSHDATA1"),
+            (0, AsmParseErrorKind::UnknownToken)
+        );
+        assert_eq!(
+            asm_error("NOP 12345678901"),
+            (4, AsmParseErrorKind::InvalidHex)
+        );
+        assert_eq!(
+            asm_error("0102[ANY]"),
+            (0, AsmParseErrorKind::UnknownSighashType)
+        );
+        assert_eq!(asm_error("01x2[ALL]"), (0, AsmParseErrorKind::InvalidHex));
+    }
+}
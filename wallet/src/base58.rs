@@ -6,27 +6,58 @@
 // However, I tried not to look at the code so much, and instead tried to derive
 // some things myself.
 
+use crate::network::Network;
 use crypto::{digest::Digest, sha2};
+use std::{error, fmt};
 
-const ALPHABET: &'static [u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
-
-// This is used to translate bytes in a string to their index in `ALPHABET`.
-// For example, `b"aO"` is equal to `[97, 79]`.
-// See that `CHAR_ALPHABET_INDEX_MAP[97] = 33`, which is the index of 'a' in `ALPHABET`.
-// Also, note that `CHAR_ALPHABET_INDEX_MAP[79] = -1`, since `O` is not in `ALPHABET`.
-const CHAR_ALPHABET_INDEX_MAP: &'static [i8; 256] = &[
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, 0, 1, 2, 3, 4, 5, 6, 7, 8, -1, -1, -1, -1, -1, -1, -1, 9, 10, 11, 12, 13, 14, 15, 16, -1,
-    17, 18, 19, 20, 21, -1, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, -1, -1, -1, -1, -1, -1, 33,
-    34, 35, 36, 37, 38, 39, 40, 41, 42, 43, -1, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56,
-    57, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-    -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-];
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alphabet {
+    characters: [u8; 58],
+    // This is used to translate bytes in a string to their index in `characters`.
+    // For example, `b"aO"` is equal to `[97, 79]`. For the Bitcoin alphabet,
+    // `indexes[97] = 33`, which is the index of 'a' in `characters`, and
+    // `indexes[79] = -1`, since `O` is not in `characters`.
+    indexes: [i8; 256],
+}
+
+impl Alphabet {
+    pub const BITCOIN: Alphabet =
+        Alphabet::new(b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz");
+    pub const RIPPLE: Alphabet =
+        Alphabet::new(b"rpshnaf39wBUDNEGHJKLM4PQRST7VWXYZ2bcdeCg65jkm8oFqi1tuvAxyz");
+    pub const FLICKR: Alphabet =
+        Alphabet::new(b"123456789abcdefghijkmnopqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ");
+
+    // Panics (at compile time, when used in a const) if a character is not
+    // ASCII or is repeated.
+    pub const fn new(characters: &[u8; 58]) -> Alphabet {
+        let mut indexes = [-1; 256];
+
+        let mut i = 0;
+        while i < characters.len() {
+            let c = characters[i] as usize;
+            if c >= 128 {
+                panic!("base58 alphabets must be ASCII");
+            }
+            if indexes[c] != -1 {
+                panic!("base58 alphabets can't repeat characters");
+            }
+            indexes[c] = i as i8;
+            i += 1;
+        }
+
+        Alphabet {
+            characters: *characters,
+            indexes,
+        }
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Alphabet::BITCOIN
+    }
+}
 
 fn double_sha256(payload: &[u8]) -> [u8; 32] {
     let mut final_hash: [u8; 32] = [0; 32];
@@ -41,179 +72,582 @@ fn double_sha256(payload: &[u8]) -> [u8; 32] {
 }
 
 #[derive(Debug, PartialEq)]
-enum FromBase58Error {
-    InvalidCharacter,
+pub enum FromBase58Error {
+    // `index` is the byte offset of `character` in the input
+    InvalidCharacter { character: char, index: usize },
 }
 
-trait ToBase58 {
-    fn to_base58(&self) -> String;
+impl fmt::Display for FromBase58Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromBase58Error::InvalidCharacter { character, index } => write!(
+                f,
+                "invalid base58 character {:?} at offset {}",
+                character, index
+            ),
+        }
+    }
 }
 
-trait FromBase58 {
-    fn from_base58(&self) -> Result<Vec<u8>, FromBase58Error>;
+impl error::Error for FromBase58Error {}
+
+#[derive(Debug, PartialEq)]
+pub enum ToBase58Error {
+    // `required` is the full size of the encoding
+    BufferTooSmall { required: usize },
 }
 
-impl ToBase58 for [u8] {
-    // After all the initial zeros are counted, what follows is just long
-    // division.
+impl fmt::Display for ToBase58Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ToBase58Error::BufferTooSmall { required } => write!(
+                f,
+                "output buffer is too small, {} bytes are required",
+                required
+            ),
+        }
+    }
+}
+
+impl error::Error for ToBase58Error {}
+
+// Every method without an alphabet uses `Alphabet::BITCOIN`.
+pub trait ToBase58 {
+    fn to_base58_with(&self, alphabet: &Alphabet) -> String;
+
+    // Writes the encoding at the start of `output` and returns its size.
+    fn to_base58_slice_with(
+        &self,
+        alphabet: &Alphabet,
+        output: &mut [u8],
+    ) -> Result<usize, ToBase58Error>;
+
+    fn write_base58_with<W: fmt::Write>(&self, alphabet: &Alphabet, writer: &mut W) -> fmt::Result;
+
     fn to_base58(&self) -> String {
-        let mut zero_counter: usize = 0;
-        let mut encoding_flag = false;
-
-        let mut b58_bytes: Vec<u32> = Vec::new();
-
-        let mut first_b58_byte_iter = true;
-        let mut size = 0;
-        for &byte in self.iter() {
-            if !encoding_flag {
-                zero_counter += (byte == 0) as usize;
-                if byte == 0 {
-                    continue;
-                }
-                encoding_flag = true;
-            }
+        self.to_base58_with(&Alphabet::BITCOIN)
+    }
 
-            let mut carry = byte as u32;
-            let mut i = 0;
+    fn to_base58_slice(&self, output: &mut [u8]) -> Result<usize, ToBase58Error> {
+        self.to_base58_slice_with(&Alphabet::BITCOIN, output)
+    }
 
-            while carry != 0 || i < size {
-                if first_b58_byte_iter {
-                    b58_bytes.push(0);
-                    first_b58_byte_iter = false;
-                } else {
-                    if let Some(b58_byte) = b58_bytes.get(i) {
-                        carry += b58_byte * 256;
-                    } else {
-                        b58_bytes.push(0);
-                    }
-                }
+    fn write_base58<W: fmt::Write>(&self, writer: &mut W) -> fmt::Result {
+        self.write_base58_with(&Alphabet::BITCOIN, writer)
+    }
+}
 
-                b58_bytes[i] = carry % 58;
-                carry /= 58;
+pub trait FromBase58 {
+    fn from_base58_with(&self, alphabet: &Alphabet) -> Result<Vec<u8>, FromBase58Error>;
 
-                i += 1;
-            }
+    fn from_base58(&self) -> Result<Vec<u8>, FromBase58Error> {
+        self.from_base58_with(&Alphabet::BITCOIN)
+    }
+}
+
+// Instead of working with one base58 digit (or one byte) at a time, numbers are
+// kept in limbs that hold 10 base58 digits each when encoding, and 4 bytes
+// each when decoding. 58^10 still fits in a u64, and every intermediate
+// product fits in a u128.
+const LIMB_BASE: u64 = 430_804_206_899_405_824; // 58^10
+const LIMB_DIGITS: usize = 10;
+
+// Inputs that need at most this many limbs don't touch the heap. That is
+// enough for anything up to ~200 bytes, which covers addresses, WIFs and
+// extended keys.
+const STACK_LIMBS: usize = 32;
+
+fn with_scratch<R>(limbs: usize, f: impl FnOnce(&mut [u64]) -> R) -> R {
+    if limbs <= STACK_LIMBS {
+        f(&mut [0; STACK_LIMBS][..limbs])
+    } else {
+        f(&mut vec![0; limbs])
+    }
+}
+
+// Converts big-endian `input` to base 58^10, least significant limb first,
+// feeding it 4 bytes at a time. Returns how many limbs are in use.
+fn bytes_to_limbs(input: &[u8], limbs: &mut [u64]) -> usize {
+    let head = input.len() % 4;
+    let chunks = Some(&input[..head])
+        .filter(|chunk| !chunk.is_empty())
+        .into_iter()
+        .chain(input[head..].chunks(4));
+
+    let mut size = 0;
+    for chunk in chunks {
+        let shift = 8 * chunk.len() as u32;
+        let mut carry = chunk.iter().fold(0, |acc, &byte| acc << 8 | byte as u64);
 
-            size = i;
+        for limb in limbs[..size].iter_mut() {
+            let value = ((*limb as u128) << shift) | carry as u128;
+            *limb = (value % LIMB_BASE as u128) as u64;
+            carry = (value / LIMB_BASE as u128) as u64;
         }
 
-        let encoding_length = zero_counter as usize + b58_bytes.len();
-        let mut b58_encoding: Vec<u8> = vec![b'1'; encoding_length];
+        while carry != 0 {
+            limbs[size] = carry % LIMB_BASE;
+            carry /= LIMB_BASE;
+            size += 1;
+        }
+    }
+
+    size
+}
+
+// Writes the 10 digits of `limb` into `digits` and returns how many of them
+// are significant (i.e. not leading zeros).
+fn limb_to_digits(alphabet: &Alphabet, mut limb: u64, digits: &mut [u8; LIMB_DIGITS]) -> usize {
+    let mut significant = 0;
+    for (i, digit) in digits.iter_mut().rev().enumerate() {
+        *digit = alphabet.characters[(limb % 58) as usize];
+        limb /= 58;
+        if *digit != alphabet.characters[0] {
+            significant = i + 1;
+        }
+    }
+
+    significant
+}
+
+// Where the encoder puts its output. `reserve` is called once with the final
+// size, before any call to `write`.
+trait Base58Writer {
+    type Error;
+
+    fn reserve(&mut self, size: usize) -> Result<(), Self::Error>;
 
-        for (i, &b58_byte) in b58_bytes.iter().rev().enumerate() {
-            b58_encoding[zero_counter + i] = ALPHABET[b58_byte as usize];
+    fn write(&mut self, digits: &[u8]) -> Result<(), Self::Error>;
+}
+
+struct SliceWriter<'a> {
+    output: &'a mut [u8],
+    position: usize,
+}
+
+impl Base58Writer for SliceWriter<'_> {
+    type Error = ToBase58Error;
+
+    fn reserve(&mut self, size: usize) -> Result<(), Self::Error> {
+        if self.output.len() < size {
+            return Err(ToBase58Error::BufferTooSmall { required: size });
         }
+        Ok(())
+    }
 
-        String::from_utf8(b58_encoding).unwrap()
+    fn write(&mut self, digits: &[u8]) -> Result<(), Self::Error> {
+        self.output[self.position..self.position + digits.len()].copy_from_slice(digits);
+        self.position += digits.len();
+        Ok(())
     }
 }
 
-impl FromBase58 for str {
-    fn from_base58(&self) -> Result<Vec<u8>, FromBase58Error> {
-        let mut raw_bytes: Vec<u8> = Vec::new();
-        let mut final_bytes: Vec<u8> = Vec::new();
+struct FmtWriter<'a, W: fmt::Write>(&'a mut W);
 
-        let mut encoding_flag = false;
+impl<W: fmt::Write> Base58Writer for FmtWriter<'_, W> {
+    type Error = fmt::Error;
 
-        let mut first_byte_iter = true;
-        let mut size = 0;
-        for &byte in self.as_bytes().iter() {
-            let b58_value = CHAR_ALPHABET_INDEX_MAP[byte as usize];
-            if b58_value == -1 {
-                return Err(FromBase58Error::InvalidCharacter);
-            }
+    fn reserve(&mut self, _size: usize) -> Result<(), Self::Error> {
+        Ok(())
+    }
 
-            if !encoding_flag {
-                if b58_value == 0 {
-                    final_bytes.push(0);
-                    continue;
-                }
-                encoding_flag = true;
-            }
+    fn write(&mut self, digits: &[u8]) -> Result<(), Self::Error> {
+        // every byte comes from an `Alphabet`, so this is always ASCII
+        self.0.write_str(std::str::from_utf8(digits).unwrap())
+    }
+}
 
-            let mut carry = b58_value as u32;
-            let mut i = 0;
+// Each leading zero byte is encoded as a leading '1', and the rest is just a
+// change of base.
+fn encode<W: Base58Writer>(
+    input: &[u8],
+    alphabet: &Alphabet,
+    writer: &mut W,
+) -> Result<(), W::Error> {
+    let zero_counter = input.iter().take_while(|&&byte| byte == 0).count();
+    let input = &input[zero_counter..];
 
-            while carry != 0 || i < size {
-                if first_byte_iter {
-                    raw_bytes.push(0);
-                    first_byte_iter = false;
-                } else {
-                    if let Some(raw_byte) = raw_bytes.get(i) {
-                        carry += *raw_byte as u32 * 58;
-                    } else {
-                        raw_bytes.push(0);
+    with_scratch(input.len() / 7 + 1, |limbs| {
+        let size = bytes_to_limbs(input, limbs);
+        let limbs = &limbs[..size];
+
+        let mut digits = [0; LIMB_DIGITS];
+        let top_digits = match limbs.last() {
+            Some(&top) => limb_to_digits(alphabet, top, &mut digits),
+            None => 0,
+        };
+
+        writer.reserve(zero_counter + top_digits + LIMB_DIGITS * size.saturating_sub(1))?;
+
+        for _ in 0..zero_counter {
+            writer.write(&alphabet.characters[..1])?;
+        }
+        writer.write(&digits[LIMB_DIGITS - top_digits..])?;
+        for &limb in limbs.iter().rev().skip(1) {
+            limb_to_digits(alphabet, limb, &mut digits);
+            writer.write(&digits)?;
+        }
+
+        Ok(())
+    })
+}
+
+impl ToBase58 for [u8] {
+    fn to_base58_with(&self, alphabet: &Alphabet) -> String {
+        let mut encoded = String::with_capacity(self.len() * 138 / 100 + 1);
+        self.write_base58_with(alphabet, &mut encoded).unwrap();
+        encoded
+    }
+
+    fn to_base58_slice_with(
+        &self,
+        alphabet: &Alphabet,
+        output: &mut [u8],
+    ) -> Result<usize, ToBase58Error> {
+        let mut writer = SliceWriter {
+            output,
+            position: 0,
+        };
+        encode(self, alphabet, &mut writer)?;
+        Ok(writer.position)
+    }
+
+    fn write_base58_with<W: fmt::Write>(&self, alphabet: &Alphabet, writer: &mut W) -> fmt::Result {
+        encode(self, alphabet, &mut FmtWriter(writer))
+    }
+}
+
+impl FromBase58 for str {
+    // The reverse of `encode`: leading '1's become zero bytes, and the
+    // remaining digits are read 10 at a time into 32-bit limbs.
+    fn from_base58_with(&self, alphabet: &Alphabet) -> Result<Vec<u8>, FromBase58Error> {
+        let encoded = self.as_bytes();
+
+        let zero_counter = encoded
+            .iter()
+            .take_while(|&&c| c == alphabet.characters[0])
+            .count();
+        let digits = &encoded[zero_counter..];
+
+        with_scratch(digits.len() / 5 + 1, |limbs| {
+            let head = digits.len() % LIMB_DIGITS;
+            let chunks = Some(&digits[..head])
+                .filter(|chunk| !chunk.is_empty())
+                .into_iter()
+                .chain(digits[head..].chunks(LIMB_DIGITS));
+
+            let mut size = 0;
+            let mut offset = zero_counter;
+            for chunk in chunks {
+                let mut value: u64 = 0;
+                let mut multiplier: u64 = 1;
+                for &c in chunk {
+                    let b58_value = alphabet.indexes[c as usize];
+                    if b58_value == -1 {
+                        // everything before `offset` is ASCII, so it is a char boundary
+                        let character = self[offset..].chars().next().unwrap();
+                        return Err(FromBase58Error::InvalidCharacter {
+                            character,
+                            index: offset,
+                        });
                     }
+                    value = value * 58 + b58_value as u64;
+                    multiplier *= 58;
+                    offset += 1;
                 }
 
-                raw_bytes[i] = (carry % 256) as u8;
-                carry /= 256;
+                let mut carry = value;
+                for limb in limbs[..size].iter_mut() {
+                    let product = *limb as u128 * multiplier as u128 + carry as u128;
+                    *limb = product as u32 as u64;
+                    carry = (product >> 32) as u64;
+                }
 
-                i += 1
+                while carry != 0 {
+                    limbs[size] = carry & 0xffff_ffff;
+                    carry >>= 32;
+                    size += 1;
+                }
             }
 
-            size = i;
-        }
+            let mut decoded = vec![0; zero_counter];
+            decoded.reserve(4 * size);
+            for (i, limb) in limbs[..size].iter().rev().enumerate() {
+                let bytes = (*limb as u32).to_be_bytes();
+                if i == 0 {
+                    let leading_zeros = bytes.iter().take_while(|&&byte| byte == 0).count();
+                    decoded.extend_from_slice(&bytes[leading_zeros..]);
+                } else {
+                    decoded.extend_from_slice(&bytes);
+                }
+            }
 
-        raw_bytes.reverse();
-        final_bytes.extend_from_slice(&raw_bytes);
-        Ok(final_bytes)
+            Ok(decoded)
+        })
     }
 }
 
 #[derive(Debug, PartialEq)]
-enum FromBase58CheckError {
+pub enum FromBase58CheckError {
     Base58Error(FromBase58Error),
     InvalidCheckSum,
+    // the decoded bytes cannot even hold the 4 bytes of the checksum
+    TooShort,
+    // `length` is the size of the payload after the version prefix was removed
+    InvalidLength {
+        version: Base58CheckVersion,
+        length: usize,
+    },
+    // the data decoded fine, but its version is not the one the caller needs
+    UnexpectedVersion(Option<(Base58CheckVersion, Network)>),
 }
 
-#[derive(Debug, PartialEq)]
-enum Base58CheckVersion {
+impl fmt::Display for FromBase58CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromBase58CheckError::Base58Error(err) => write!(f, "{}", err),
+            FromBase58CheckError::InvalidCheckSum => write!(f, "invalid base58check checksum"),
+            FromBase58CheckError::TooShort => {
+                write!(f, "base58check data is shorter than its checksum")
+            }
+            FromBase58CheckError::InvalidLength { version, length } => write!(
+                f,
+                "invalid payload length {} for version {:?}",
+                length, version
+            ),
+            FromBase58CheckError::UnexpectedVersion(version) => {
+                write!(f, "unexpected version {:?}", version)
+            }
+        }
+    }
+}
+
+impl error::Error for FromBase58CheckError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FromBase58CheckError::Base58Error(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FromBase58Error> for FromBase58CheckError {
+    fn from(err: FromBase58Error) -> Self {
+        FromBase58CheckError::Base58Error(err)
+    }
+}
+
+// Script types that SLIP-132 attaches to extended keys through their version
+// bytes. `P2PKH` is the plain BIP32 `xpub`/`tpub`, which is also used for P2SH.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ExtendedKeyScriptType {
+    P2PKH,
+    P2SHP2WPKH,
+    P2SHP2WSH,
+    P2WPKH,
+    P2WSH,
+}
+
+// Taken from https://github.com/satoshilabs/slips/blob/master/slip-0132.md
+// Each entry is (script type, network, public version, private version).
+const EXTENDED_KEY_VERSIONS: [(ExtendedKeyScriptType, Network, [u8; 4], [u8; 4]); 10] = [
+    // xpub / xprv
+    (
+        ExtendedKeyScriptType::P2PKH,
+        Network::Bitcoin,
+        [0x04, 0x88, 0xB2, 0x1E],
+        [0x04, 0x88, 0xAD, 0xE4],
+    ),
+    // ypub / yprv
+    (
+        ExtendedKeyScriptType::P2SHP2WPKH,
+        Network::Bitcoin,
+        [0x04, 0x9D, 0x7C, 0xB2],
+        [0x04, 0x9D, 0x78, 0x78],
+    ),
+    // Ypub / Yprv
+    (
+        ExtendedKeyScriptType::P2SHP2WSH,
+        Network::Bitcoin,
+        [0x02, 0x95, 0xB4, 0x3F],
+        [0x02, 0x95, 0xB0, 0x05],
+    ),
+    // zpub / zprv
+    (
+        ExtendedKeyScriptType::P2WPKH,
+        Network::Bitcoin,
+        [0x04, 0xB2, 0x47, 0x46],
+        [0x04, 0xB2, 0x43, 0x0C],
+    ),
+    // Zpub / Zprv
+    (
+        ExtendedKeyScriptType::P2WSH,
+        Network::Bitcoin,
+        [0x02, 0xAA, 0x7E, 0xD3],
+        [0x02, 0xAA, 0x7A, 0x99],
+    ),
+    // tpub / tprv
+    (
+        ExtendedKeyScriptType::P2PKH,
+        Network::Testnet,
+        [0x04, 0x35, 0x87, 0xCF],
+        [0x04, 0x35, 0x83, 0x94],
+    ),
+    // upub / uprv
+    (
+        ExtendedKeyScriptType::P2SHP2WPKH,
+        Network::Testnet,
+        [0x04, 0x4A, 0x52, 0x62],
+        [0x04, 0x4A, 0x4E, 0x28],
+    ),
+    // Upub / Uprv
+    (
+        ExtendedKeyScriptType::P2SHP2WSH,
+        Network::Testnet,
+        [0x02, 0x42, 0x89, 0xEF],
+        [0x02, 0x42, 0x85, 0xB5],
+    ),
+    // vpub / vprv
+    (
+        ExtendedKeyScriptType::P2WPKH,
+        Network::Testnet,
+        [0x04, 0x5F, 0x1C, 0xF6],
+        [0x04, 0x5F, 0x18, 0xBC],
+    ),
+    // Vpub / Vprv
+    (
+        ExtendedKeyScriptType::P2WSH,
+        Network::Testnet,
+        [0x02, 0x57, 0x54, 0x83],
+        [0x02, 0x57, 0x50, 0x48],
+    ),
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Base58CheckVersion {
     PKH,
     SH,
     SKWIF,
     SKBIP38,
-    PKBIP32,
+    PKBIP32(ExtendedKeyScriptType),
+    SKBIP32(ExtendedKeyScriptType),
 }
 
 impl Base58CheckVersion {
-    fn to_vec(&self) -> Vec<u8> {
-        match self {
-            Base58CheckVersion::PKH => vec![0x00],
-            Base58CheckVersion::SH => vec![0x05],
-            Base58CheckVersion::SKWIF => vec![0x80],
-            Base58CheckVersion::SKBIP38 => vec![0x01, 0x42],
-            Base58CheckVersion::PKBIP32 => vec![0x04, 0x88, 0xB2, 0x1E],
+    // BIP38 only defines a mainnet prefix, so it is the same for every network.
+    pub fn to_vec(&self, network: Network) -> Vec<u8> {
+        match (self, network.is_mainnet()) {
+            (Base58CheckVersion::PKH, true) => vec![0x00],
+            (Base58CheckVersion::PKH, false) => vec![0x6f],
+            (Base58CheckVersion::SH, true) => vec![0x05],
+            (Base58CheckVersion::SH, false) => vec![0xc4],
+            (Base58CheckVersion::SKWIF, true) => vec![0x80],
+            (Base58CheckVersion::SKWIF, false) => vec![0xef],
+            (Base58CheckVersion::SKBIP38, _) => vec![0x01, 0x42],
+            (Base58CheckVersion::PKBIP32(script_type), _)
+            | (Base58CheckVersion::SKBIP32(script_type), _) => {
+                let is_private = matches!(self, Base58CheckVersion::SKBIP32(_));
+                let network = if network.is_mainnet() {
+                    Network::Bitcoin
+                } else {
+                    Network::Testnet
+                };
+
+                EXTENDED_KEY_VERSIONS
+                    .iter()
+                    .find(|(t, n, _, _)| t == script_type && *n == network)
+                    .map(|(_, _, public, private)| {
+                        if is_private {
+                            private.to_vec()
+                        } else {
+                            public.to_vec()
+                        }
+                    })
+                    .unwrap()
+            }
         }
     }
 
-    fn get_version(payload_with_version: &[u8]) -> Option<Self> {
+    fn get_extended_key_version(payload_with_version: &[u8]) -> Option<(Self, Network)> {
+        let prefix = payload_with_version.get(..4)?;
+
+        EXTENDED_KEY_VERSIONS
+            .iter()
+            .find_map(|(script_type, network, public, private)| {
+                if prefix == public {
+                    Some((Base58CheckVersion::PKBIP32(*script_type), *network))
+                } else if prefix == private {
+                    Some((Base58CheckVersion::SKBIP32(*script_type), *network))
+                } else {
+                    None
+                }
+            })
+    }
+
+    // Testnet, signet and regtest share their prefixes, so all of them are
+    // reported as `Network::Testnet`.
+    pub fn get_version(payload_with_version: &[u8]) -> Option<(Self, Network)> {
         match payload_with_version {
-            [0x00, ..] => Some(Base58CheckVersion::PKH),
-            [0x05, ..] => Some(Base58CheckVersion::SH),
-            [0x80, ..] => Some(Base58CheckVersion::SKWIF),
-            [0x01, 0x42, ..] => Some(Base58CheckVersion::SKBIP38),
-            [0x04, 0x88, 0xB2, 0x1E, ..] => Some(Base58CheckVersion::PKBIP32),
-            _ => None,
+            [0x00, ..] => Some((Base58CheckVersion::PKH, Network::Bitcoin)),
+            [0x6f, ..] => Some((Base58CheckVersion::PKH, Network::Testnet)),
+            [0x05, ..] => Some((Base58CheckVersion::SH, Network::Bitcoin)),
+            [0xc4, ..] => Some((Base58CheckVersion::SH, Network::Testnet)),
+            [0x80, ..] => Some((Base58CheckVersion::SKWIF, Network::Bitcoin)),
+            [0xef, ..] => Some((Base58CheckVersion::SKWIF, Network::Testnet)),
+            [0x01, 0x42, ..] => Some((Base58CheckVersion::SKBIP38, Network::Bitcoin)),
+            _ => Self::get_extended_key_version(payload_with_version),
+        }
+    }
+
+    // Size of the payload that follows the version prefix:
+    // - PKH/SH: a HASH160
+    // - SKWIF: a secret key, plus the 0x01 suffix when the public key is compressed
+    // - SKBIP38: flag byte, address hash and the two encrypted halves
+    // - PKBIP32/SKBIP32: the 78-byte serialization minus the 4 bytes of the version
+    pub fn is_valid_payload_length(&self, length: usize) -> bool {
+        match self {
+            Base58CheckVersion::PKH | Base58CheckVersion::SH => length == 20,
+            Base58CheckVersion::SKWIF => length == 32 || length == 33,
+            Base58CheckVersion::SKBIP38 => length == 37,
+            Base58CheckVersion::PKBIP32(_) | Base58CheckVersion::SKBIP32(_) => length == 74,
         }
     }
 }
 
-trait ToBase58Check {
-    fn to_base58check(&self, version: Option<Base58CheckVersion>) -> String;
+// The decoded payload without its version prefix, and the version that was
+// detected, if any.
+pub type Base58CheckPayload = (Vec<u8>, Option<(Base58CheckVersion, Network)>);
+
+pub trait ToBase58Check {
+    fn to_base58check(&self, version: Option<(Base58CheckVersion, Network)>) -> String;
 }
 
-trait FromBase58Check {
-    fn from_base58check(
+pub trait FromBase58Check {
+    // Guesses the version from the leading bytes and strips it. When the rest
+    // of the payload doesn't have the size that version requires (see
+    // `Base58CheckVersion::is_valid_payload_length`), fails with
+    // `InvalidLength`; use `from_base58check_raw` for versionless data.
+    fn from_base58check(&self) -> Result<Base58CheckPayload, FromBase58CheckError>;
+
+    // Verifies the checksum and returns everything before it, without ever
+    // stripping a version prefix.
+    fn from_base58check_raw(&self) -> Result<Vec<u8>, FromBase58CheckError>;
+
+    // Requires the payload to start with the prefix of `version` and to have
+    // the size that version requires. Only the payload after the prefix is
+    // returned.
+    fn from_base58check_expect(
         &self,
-    ) -> Result<(Vec<u8>, Option<Base58CheckVersion>), FromBase58CheckError>;
+        version: (Base58CheckVersion, Network),
+    ) -> Result<Vec<u8>, FromBase58CheckError>;
 }
 
 impl ToBase58Check for [u8] {
-    fn to_base58check(&self, version: Option<Base58CheckVersion>) -> String {
+    fn to_base58check(&self, version: Option<(Base58CheckVersion, Network)>) -> String {
         let mut final_vec = match version {
             None => vec![],
-            Some(v) => v.to_vec(),
+            Some((v, network)) => v.to_vec(network),
         };
 
         final_vec.extend_from_slice(self);
@@ -227,39 +661,95 @@ impl ToBase58Check for [u8] {
 }
 
 impl FromBase58Check for str {
-    fn from_base58check(
-        &self,
-    ) -> Result<(Vec<u8>, Option<Base58CheckVersion>), FromBase58CheckError> {
-        let decoded = self
-            .from_base58()
-            .map_err(FromBase58CheckError::Base58Error)?;
+    fn from_base58check(&self) -> Result<Base58CheckPayload, FromBase58CheckError> {
+        let mut payload_with_version = self.from_base58check_raw()?;
+
+        let version = Base58CheckVersion::get_version(&payload_with_version);
+
+        if let Some((v, network)) = version {
+            payload_with_version.drain(..v.to_vec(network).len());
+            if !v.is_valid_payload_length(payload_with_version.len()) {
+                return Err(FromBase58CheckError::InvalidLength {
+                    version: v,
+                    length: payload_with_version.len(),
+                });
+            }
+        }
+
+        Ok((payload_with_version, version))
+    }
+
+    fn from_base58check_raw(&self) -> Result<Vec<u8>, FromBase58CheckError> {
+        let mut decoded = self.from_base58()?;
 
         let decoded_size = decoded.len();
+        if decoded_size < 4 {
+            return Err(FromBase58CheckError::TooShort);
+        }
 
-        let payload_with_version = &decoded[..decoded_size - 4];
-        let checksum = &decoded[decoded_size - 4..];
+        let checksum = decoded.split_off(decoded_size - 4);
 
-        let final_hash = double_sha256(payload_with_version);
+        let final_hash = double_sha256(&decoded);
 
-        if checksum != &final_hash[0..4] {
+        if checksum != final_hash[0..4] {
             return Err(FromBase58CheckError::InvalidCheckSum);
         }
 
-        let version = Base58CheckVersion::get_version(payload_with_version);
+        Ok(decoded)
+    }
 
-        let payload_without_version = match version {
-            None => payload_with_version,
-            Some(Base58CheckVersion::PKH)
-            | Some(Base58CheckVersion::SH)
-            | Some(Base58CheckVersion::SKWIF) => &payload_with_version[1..],
-            Some(Base58CheckVersion::SKBIP38) => &payload_with_version[2..],
-            Some(Base58CheckVersion::PKBIP32) => &payload_with_version[4..],
-        };
+    fn from_base58check_expect(
+        &self,
+        version: (Base58CheckVersion, Network),
+    ) -> Result<Vec<u8>, FromBase58CheckError> {
+        let mut payload_with_version = self.from_base58check_raw()?;
+
+        let (v, network) = version;
+        let prefix = v.to_vec(network);
 
-        Ok((payload_without_version.to_vec(), version))
+        if !payload_with_version.starts_with(&prefix) {
+            return Err(FromBase58CheckError::UnexpectedVersion(
+                Base58CheckVersion::get_version(&payload_with_version),
+            ));
+        }
+
+        let payload = payload_with_version.split_off(prefix.len());
+        if !v.is_valid_payload_length(payload.len()) {
+            return Err(FromBase58CheckError::InvalidLength {
+                version: v,
+                length: payload.len(),
+            });
+        }
+
+        Ok(payload)
     }
 }
 
+// Re-encodes an extended key with the SLIP-132 prefix of `script_type`,
+// keeping its network and whether it is public or private. The script type
+// implied by the prefix of `key` is returned along with the new encoding.
+pub fn convert_extended_key(
+    key: &str,
+    script_type: ExtendedKeyScriptType,
+) -> Result<(String, ExtendedKeyScriptType), FromBase58CheckError> {
+    let (payload, version) = key.from_base58check()?;
+
+    let (converted_version, implied_script_type) = match version {
+        Some((Base58CheckVersion::PKBIP32(t), network)) => {
+            ((Base58CheckVersion::PKBIP32(script_type), network), t)
+        }
+        Some((Base58CheckVersion::SKBIP32(t), network)) => {
+            ((Base58CheckVersion::SKBIP32(script_type), network), t)
+        }
+        _ => return Err(FromBase58CheckError::UnexpectedVersion(version)),
+    };
+
+    Ok((
+        payload.to_base58check(Some(converted_version)),
+        implied_script_type,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     // Tests cases come from the following sources:
@@ -298,6 +788,132 @@ mod tests {
         assert_eq!(hex!("000111d38e5fc9071ffcd20b4a763cc9ae4f252bb4e48fd66a835e252ada93ff480d6dd43dc62a641155a5").to_base58(), "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz");
     }
 
+    #[test]
+    fn to_base58_slice_and_writer() {
+        let input = hex!("00eb15231dfceb60925886b67d065299925915aeb172c06647");
+        let expected = "1NS17iag9jJgTHD1VXjvLCEnZuQ3rJDE9L";
+
+        let mut output = [0; 64];
+        let size = input.to_base58_slice(&mut output).unwrap();
+        assert_eq!(&output[..size], expected.as_bytes());
+
+        let mut exact = [0; 34];
+        assert_eq!(input.to_base58_slice(&mut exact).unwrap(), 34);
+        assert_eq!(
+            input.to_base58_slice(&mut [0; 33]).unwrap_err(),
+            ToBase58Error::BufferTooSmall { required: 34 }
+        );
+        assert_eq!(b"".to_base58_slice(&mut []).unwrap(), 0);
+
+        let mut written = String::from("address: ");
+        input.write_base58(&mut written).unwrap();
+        assert_eq!(written, format!("address: {}", expected));
+    }
+
+    // The straightforward digit-by-digit conversion, to check the limb-based
+    // codec against.
+    fn naive_to_base58(input: &[u8]) -> String {
+        let zero_counter = input.iter().take_while(|&&byte| byte == 0).count();
+        let mut digits: Vec<u32> = Vec::new();
+        for &byte in &input[zero_counter..] {
+            let mut carry = byte as u32;
+            for digit in digits.iter_mut() {
+                carry += *digit * 256;
+                *digit = carry % 58;
+                carry /= 58;
+            }
+            while carry != 0 {
+                digits.push(carry % 58);
+                carry /= 58;
+            }
+        }
+
+        let mut encoded = "1".repeat(zero_counter);
+        encoded.extend(
+            digits
+                .iter()
+                .rev()
+                .map(|&d| Alphabet::BITCOIN.characters[d as usize] as char),
+        );
+        encoded
+    }
+
+    #[test]
+    fn base58_matches_naive_conversion() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(58);
+
+        for size in 0..300 {
+            let mut input = vec![0; size];
+            rng.fill(&mut input[..]);
+            // exercise the leading zeros too
+            let zeros = rng.gen_range(0..=size.min(3));
+            input[..zeros].iter_mut().for_each(|byte| *byte = 0);
+
+            let encoded = input.to_base58();
+            assert_eq!(encoded, naive_to_base58(&input));
+            assert_eq!(encoded.from_base58().unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn other_alphabets() {
+        // the all-zero Ripple account ("ACCOUNT_ZERO")
+        let mut account_zero = vec![0; 21];
+        account_zero.extend_from_slice(&double_sha256(&account_zero)[..4]);
+        assert_eq!(
+            account_zero.to_base58_with(&Alphabet::RIPPLE),
+            "rrrrrrrrrrrrrrrrrrrrrhoLvTp"
+        );
+        assert_eq!(
+            "rrrrrrrrrrrrrrrrrrrrrhoLvTp"
+                .from_base58_with(&Alphabet::RIPPLE)
+                .unwrap(),
+            account_zero
+        );
+
+        assert_eq!(
+            b"hello world".to_base58_with(&Alphabet::RIPPLE),
+            "StVrDLaUATiyKyV"
+        );
+        assert_eq!(
+            b"\0\0hello world".to_base58_with(&Alphabet::FLICKR),
+            "11rTu1dk6cWsRYjYu"
+        );
+        assert_eq!(
+            "11rTu1dk6cWsRYjYu"
+                .from_base58_with(&Alphabet::FLICKR)
+                .unwrap(),
+            b"\0\0hello world".to_vec()
+        );
+
+        let mut output = [0; 32];
+        let size = b"hello world"
+            .to_base58_slice_with(&Alphabet::FLICKR, &mut output)
+            .unwrap();
+        assert_eq!(&output[..size], b"rTu1dk6cWsRYjYu");
+
+        // '0' is in none of them, and '1' only means zero in some
+        assert_eq!(
+            "r0".from_base58_with(&Alphabet::RIPPLE).unwrap_err(),
+            FromBase58Error::InvalidCharacter {
+                character: '0',
+                index: 1
+            }
+        );
+        assert_eq!("1".from_base58().unwrap(), vec![0]);
+        assert_eq!("1".from_base58_with(&Alphabet::RIPPLE).unwrap(), vec![50]);
+
+        const CUSTOM: Alphabet =
+            Alphabet::new(b"zyxwvutsrqponmkjihgfedcbaZYXWVUTSRQPNMLKJHGFEDCBA987654321");
+        let encoded = b"\0hello world".to_base58_with(&CUSTOM);
+        assert!(encoded.starts_with('z'));
+        assert_eq!(
+            encoded.from_base58_with(&CUSTOM).unwrap(),
+            b"\0hello world".to_vec()
+        );
+    }
+
     #[test]
     fn from_base58_correct() {
         assert_eq!(
@@ -328,31 +944,46 @@ mod tests {
             "xyz0"
                 .from_base58()
                 .expect_err("Wrong character should return an error"),
-            FromBase58Error::InvalidCharacter
+            FromBase58Error::InvalidCharacter {
+                character: '0',
+                index: 3
+            }
         );
         assert_eq!(
             "2yGEbw0RKGAsJ2HmJv"
                 .from_base58()
                 .expect_err("Wrong character should return an error"),
-            FromBase58Error::InvalidCharacter
+            FromBase58Error::InvalidCharacter {
+                character: '0',
+                index: 6
+            }
         );
         assert_eq!(
             "2yGEbwRKGOAsJ2HmJv"
                 .from_base58()
                 .expect_err("Wrong character should return an error"),
-            FromBase58Error::InvalidCharacter
+            FromBase58Error::InvalidCharacter {
+                character: 'O',
+                index: 9
+            }
         );
         assert_eq!(
             "2yGEbwRKGAsJ2HImJv"
                 .from_base58()
                 .expect_err("Wrong character should return an error"),
-            FromBase58Error::InvalidCharacter
+            FromBase58Error::InvalidCharacter {
+                character: 'I',
+                index: 14
+            }
         );
         assert_eq!(
             "2yGEbwRKGAsJ2HlmJv"
                 .from_base58()
                 .expect_err("Wrong character should return an error"),
-            FromBase58Error::InvalidCharacter
+            FromBase58Error::InvalidCharacter {
+                character: 'l',
+                index: 14
+            }
         );
     }
 
@@ -360,38 +991,38 @@ mod tests {
     fn to_base58check() {
         assert_eq!(
             hex!("1e99423a4ed27608a15a2616a2b0e9e52ced330ac530edcc32c8ffc6a526aedd")
-                .to_base58check(Some(Base58CheckVersion::SKWIF)),
+                .to_base58check(Some((Base58CheckVersion::SKWIF, Network::Bitcoin))),
             "5J3mBbAH58CpQ3Y5RNJpUKPE62SQ5tfcvU2JpbnkeyhfsYB1Jcn"
         );
         assert_eq!(
             hex!("3aba4162c7251c891207b747840551a71939b0de081f85c4e44cf7c13e41daa6")
-                .to_base58check(Some(Base58CheckVersion::SKWIF)),
+                .to_base58check(Some((Base58CheckVersion::SKWIF, Network::Bitcoin))),
             "5JG9hT3beGTJuUAmCQEmNaxAuMacCTfXuw1R3FCXig23RQHMr4K"
         );
         assert_eq!(b"hello world".to_base58check(None), "3vQB7B6MrGQZaxCuFg4oh");
         assert_eq!(
             hex!("3aba4162c7251c891207b747840551a71939b0de081f85c4e44cf7c13e41daa601")
-                .to_base58check(Some(Base58CheckVersion::SKWIF)),
+                .to_base58check(Some((Base58CheckVersion::SKWIF, Network::Bitcoin))),
             "KyBsPXxTuVD82av65KZkrGrWi5qLMah5SdNq6uftawDbgKa2wv6S"
         );
         assert_eq!(
             hex!("27b5891b01da2db74cde1689a97a2acbe23d5fb1")
-                .to_base58check(Some(Base58CheckVersion::PKH)),
+                .to_base58check(Some((Base58CheckVersion::PKH, Network::Bitcoin))),
             "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3"
         );
         assert_eq!(
             hex!("09c6e71118d8f12bec6b5c61884b35677c0a0ae3")
-                .to_base58check(Some(Base58CheckVersion::PKH)),
+                .to_base58check(Some((Base58CheckVersion::PKH, Network::Bitcoin))),
             "1thMirt546nngXqyPEz532S8fLwbozud8"
         );
         assert_eq!(
             hex!("f5f2d624cfb5c3f66d06123d0829d1c9cebf770e")
-                .to_base58check(Some(Base58CheckVersion::PKH)),
+                .to_base58check(Some((Base58CheckVersion::PKH, Network::Bitcoin))),
             "1PRTTaJesdNovgne6Ehcdu1fpEdX7913CK"
         );
         assert_eq!(
             hex!("1e99423a4ed27608a15a2616a2b0e9e52ced330ac530edcc32c8ffc6a526aedd01")
-                .to_base58check(Some(Base58CheckVersion::SKWIF)),
+                .to_base58check(Some((Base58CheckVersion::SKWIF, Network::Bitcoin))),
             "KxFC1jmwwCoACiCAWZ3eXa96mBM6tb3TYzGmf6YwgdGWZgawvrtJ"
         );
     }
@@ -404,7 +1035,7 @@ mod tests {
                 .unwrap(),
             (
                 hex!("27b5891b01da2db74cde1689a97a2acbe23d5fb1").to_vec(),
-                Some(Base58CheckVersion::PKH)
+                Some((Base58CheckVersion::PKH, Network::Bitcoin))
             )
         );
         assert_eq!(
@@ -417,7 +1048,7 @@ mod tests {
                 .unwrap(),
             (
                 hex!("1e99423a4ed27608a15a2616a2b0e9e52ced330ac530edcc32c8ffc6a526aedd").to_vec(),
-                Some(Base58CheckVersion::SKWIF)
+                Some((Base58CheckVersion::SKWIF, Network::Bitcoin))
             ),
         );
         assert_eq!(
@@ -426,7 +1057,7 @@ mod tests {
                 .unwrap(),
             (
                 hex!("1e99423a4ed27608a15a2616a2b0e9e52ced330ac530edcc32c8ffc6a526aedd01").to_vec(),
-                Some(Base58CheckVersion::SKWIF)
+                Some((Base58CheckVersion::SKWIF, Network::Bitcoin))
             ),
         );
     }
@@ -439,7 +1070,322 @@ mod tests {
         );
         assert_eq!(
             "3vOB7B6MrGQZaxCuFg4oh".from_base58check().unwrap_err(),
-            FromBase58CheckError::Base58Error(FromBase58Error::InvalidCharacter)
+            FromBase58CheckError::Base58Error(FromBase58Error::InvalidCharacter {
+                character: 'O',
+                index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn base58check_networks() {
+        let cases = [
+            (
+                "mo9ncXisMeAoXwqcV5EWuyncbmCcQN4rVs",
+                hex!("53c0307d6851aa0ce7825ba883c6bd9ad242b486").to_vec(),
+                Base58CheckVersion::PKH,
+            ),
+            (
+                "2N2JD6wb56AfK4tfmM6PwdVmoYk2dCKf4Br",
+                hex!("6349a418fc4578d10a372b54b45c280cc8c4382f").to_vec(),
+                Base58CheckVersion::SH,
+            ),
+            (
+                "93DVKyFYwSN6wEo3E2fCrFPUp17FtrtNi2Lf7n4G3garFb16CRj",
+                hex!("d6bca256b5abc5602ec2e1c121a08b0da2556587430bcf7e1898af2224885203").to_vec(),
+                Base58CheckVersion::SKWIF,
+            ),
+            (
+                "tpubD6NzVbkrYhZ4XgiXtGrdW5XDAPFCL9h7we1vwNCpn8tGbBcgfVYjXyhWo4E1xkh56hjod1RhGjxbaTLV3X4FyWuejifB9jusQ46QzG87VKp",
+                hex!("000000000000000000873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d5080339a36013301597daef41fbe593a02cc513d0b55527ec2df1050e2e8ff49c85c2").to_vec(),
+                Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+            ),
+        ];
+
+        for (encoded, payload, version) in cases.iter() {
+            assert_eq!(
+                encoded.from_base58check().unwrap(),
+                (payload.clone(), Some((*version, Network::Testnet)))
+            );
+            for network in [Network::Testnet, Network::Signet, Network::Regtest].iter() {
+                assert_eq!(payload.to_base58check(Some((*version, *network))), *encoded);
+            }
+        }
+
+        let xpub = "xpub661MyMwAqRbcFtXgS5sYJABqqG9YLmC4Q1Rdap9gSE8NqtwybGhePY2gZ29ESFjqJoCu1Rupje8YtGqsefD265TMg7usUDFdp6W1EGMcet8";
+        let (payload, version) = xpub.from_base58check().unwrap();
+        assert_eq!(
+            version,
+            Some((
+                Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+                Network::Bitcoin
+            ))
+        );
+        assert_eq!(payload.to_base58check(version), xpub);
+
+        let xprv = "xprv9s21ZrQH143K3QTDL4LXw2F7HEK3wJUD2nW2nRk4stbPy6cq3jPPqjiChkVvvNKmPGJxWUtg6LnF5kejMRNNU3TGtRBeJgk33yuGBxrMPHi";
+        let (payload, version) = xprv.from_base58check().unwrap();
+        assert_eq!(
+            version,
+            Some((
+                Base58CheckVersion::SKBIP32(ExtendedKeyScriptType::P2PKH),
+                Network::Bitcoin
+            ))
+        );
+        assert_eq!(payload.to_base58check(version), xprv);
+    }
+
+    #[test]
+    fn slip132_extended_keys() {
+        // BIP84 account key for the "abandon ... about" mnemonic
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let zprv = "zprvAdG4iTXWBoARxkkzNpNh8r6Qag3irQB8PzEMkAFeTRXxHpbF9z4QgEvBRmfvqWvGp42t42nvgGpNgYSJA9iefm1yYNZKEm7z6qUWCroSQnE";
+
+        let (payload, version) = zpub.from_base58check().unwrap();
+        assert_eq!(
+            version,
+            Some((
+                Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2WPKH),
+                Network::Bitcoin
+            ))
+        );
+        assert_eq!(payload.to_base58check(version), zpub);
+
+        let (payload, version) = zprv.from_base58check().unwrap();
+        assert_eq!(
+            version,
+            Some((
+                Base58CheckVersion::SKBIP32(ExtendedKeyScriptType::P2WPKH),
+                Network::Bitcoin
+            ))
+        );
+        assert_eq!(payload.to_base58check(version), zprv);
+
+        let conversions = [
+            (
+                ExtendedKeyScriptType::P2PKH,
+                "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V",
+            ),
+            (
+                ExtendedKeyScriptType::P2SHP2WPKH,
+                "ypub6XR9pJPUsVBFKweLeV85HtwdxjjmKEuUr6djm9mNdkh47X7ASsD6byaXFotRAKByFoWgSzCuoTjaYdrv2yoJroLAPtBuHFjVm5vNmhyNehE",
+            ),
+            (
+                ExtendedKeyScriptType::P2SHP2WSH,
+                "Ypub6iKEwY7vSSjckWoib9b47yHSgXn2Xbb5ANHQgR2v1XXTjhg5DGbNh6ST4WqtikQsVGafKaD2gg85foUgLCxG1HSqEMJJgfCVepCFMi9sffi",
+            ),
+            (ExtendedKeyScriptType::P2WPKH, zpub),
+            (
+                ExtendedKeyScriptType::P2WSH,
+                "Zpub739WFCnqb8H6bozqRWNgL4NwrVvUUDaa5UodTovoPXuLnoVJTvkwKA6b5ioUif4ntuhU53ob9LUdZ66F3uNGoX8S6gzjGa1yvYFtkDRknR2",
+            ),
+        ];
+
+        for (script_type, expected) in conversions.iter() {
+            assert_eq!(
+                convert_extended_key(zpub, *script_type).unwrap(),
+                (expected.to_string(), ExtendedKeyScriptType::P2WPKH)
+            );
+            assert_eq!(
+                convert_extended_key(expected, ExtendedKeyScriptType::P2WPKH).unwrap(),
+                (zpub.to_string(), *script_type)
+            );
+        }
+
+        assert_eq!(
+            convert_extended_key(zprv, ExtendedKeyScriptType::P2PKH).unwrap(),
+            ("xprv9ybY78BftS5UGANki6oSifuQEjkpyAC8ZmBvBNTshQnCBcxnefjHS7buPMkkqhcRzmoGZ5bokx7GuyDAiktd5HemohAU4wV1ZPMDRmLpBMm".to_string(), ExtendedKeyScriptType::P2WPKH)
+        );
+
+        // testnet keys keep their network
+        let (payload, _) = zpub.from_base58check().unwrap();
+        let tpub = payload.to_base58check(Some((
+            Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+            Network::Testnet,
+        )));
+        assert_eq!(
+            convert_extended_key(&tpub, ExtendedKeyScriptType::P2WPKH).unwrap(),
+            ("vpub5YvMuJNjRSYon44z9QmCfdf8SqJRVNvz6m55Qy5iVjZQxDfUgtiQjnc7CC1fAbED2tAGCZRERUfvtn2DstZGU6HMns6dXXH2wujSc2wfi2x".to_string(), ExtendedKeyScriptType::P2PKH)
+        );
+
+        assert_eq!(
+            convert_extended_key(
+                "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3",
+                ExtendedKeyScriptType::P2WPKH
+            )
+            .unwrap_err(),
+            FromBase58CheckError::UnexpectedVersion(Some((
+                Base58CheckVersion::PKH,
+                Network::Bitcoin
+            )))
+        );
+    }
+
+    #[test]
+    fn from_base58check_too_short() {
+        assert_eq!(
+            "".from_base58check().unwrap_err(),
+            FromBase58CheckError::TooShort
+        );
+        assert_eq!(
+            "1".from_base58check().unwrap_err(),
+            FromBase58CheckError::TooShort
+        );
+        assert_eq!(
+            "11".from_base58check().unwrap_err(),
+            FromBase58CheckError::TooShort
+        );
+        assert_eq!(
+            "1111".from_base58check().unwrap_err(),
+            FromBase58CheckError::InvalidCheckSum
+        );
+    }
+
+    #[test]
+    fn from_base58check_invalid_length() {
+        // a PKH version followed by 19 bytes instead of 20
+        let short_pkh = hex!("27b5891b01da2db74cde1689a97a2acbe23d5f")
+            .to_base58check(Some((Base58CheckVersion::PKH, Network::Bitcoin)));
+        assert_eq!(
+            short_pkh.from_base58check().unwrap_err(),
+            FromBase58CheckError::InvalidLength {
+                version: Base58CheckVersion::PKH,
+                length: 19
+            }
+        );
+        assert_eq!(
+            short_pkh
+                .from_base58check_expect((Base58CheckVersion::PKH, Network::Bitcoin))
+                .unwrap_err(),
+            FromBase58CheckError::InvalidLength {
+                version: Base58CheckVersion::PKH,
+                length: 19
+            }
+        );
+
+        let long_wif =
+            [0x11; 34].to_base58check(Some((Base58CheckVersion::SKWIF, Network::Bitcoin)));
+        assert_eq!(
+            long_wif
+                .from_base58check_expect((Base58CheckVersion::SKWIF, Network::Bitcoin))
+                .unwrap_err(),
+            FromBase58CheckError::InvalidLength {
+                version: Base58CheckVersion::SKWIF,
+                length: 34
+            }
+        );
+
+        let xpub_version = (
+            Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+            Network::Bitcoin,
+        );
+        let short_xpub = [0x11; 73].to_base58check(Some(xpub_version));
+        assert_eq!(
+            short_xpub
+                .from_base58check_expect(xpub_version)
+                .unwrap_err(),
+            FromBase58CheckError::InvalidLength {
+                version: Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+                length: 73
+            }
+        );
+
+        // the raw decoding still returns mis-sized payloads with their prefix
+        let mut raw = vec![0x00];
+        raw.extend_from_slice(&hex!("27b5891b01da2db74cde1689a97a2acbe23d5f"));
+        assert_eq!(short_pkh.from_base58check_raw().unwrap(), raw);
+    }
+
+    #[test]
+    fn from_base58check_prefix_ambiguity() {
+        // versionless payloads that happen to start with a version prefix are
+        // mis-sized for that version, only the raw decoding accepts them
+        let payloads: [(&[u8], Base58CheckVersion, usize); 3] = [
+            (b"\0hello world", Base58CheckVersion::PKH, 11),
+            (b"\x80hello world", Base58CheckVersion::SKWIF, 11),
+            (
+                b"\x04\x88\xb2\x1e",
+                Base58CheckVersion::PKBIP32(ExtendedKeyScriptType::P2PKH),
+                0,
+            ),
+        ];
+
+        for (payload, version, length) in payloads.iter() {
+            let encoded = payload.to_base58check(None);
+            assert_eq!(
+                encoded.from_base58check().unwrap_err(),
+                FromBase58CheckError::InvalidLength {
+                    version: *version,
+                    length: *length
+                }
+            );
+            assert_eq!(encoded.from_base58check_raw().unwrap(), payload.to_vec());
+        }
+
+        assert_eq!(
+            "3vQB7B6MrGQZaxCuFg4oh".from_base58check_raw().unwrap(),
+            b"hello world".to_vec()
+        );
+        assert_eq!(
+            "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3"
+                .from_base58check_raw()
+                .unwrap(),
+            hex!("0027b5891b01da2db74cde1689a97a2acbe23d5fb1").to_vec()
+        );
+    }
+
+    #[test]
+    fn from_base58check_expect() {
+        assert_eq!(
+            "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3"
+                .from_base58check_expect((Base58CheckVersion::PKH, Network::Bitcoin))
+                .unwrap(),
+            hex!("27b5891b01da2db74cde1689a97a2acbe23d5fb1").to_vec()
+        );
+        assert_eq!(
+            "mo9ncXisMeAoXwqcV5EWuyncbmCcQN4rVs"
+                .from_base58check_expect((Base58CheckVersion::PKH, Network::Regtest))
+                .unwrap(),
+            hex!("53c0307d6851aa0ce7825ba883c6bd9ad242b486").to_vec()
+        );
+        assert_eq!(
+            "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3"
+                .from_base58check_expect((Base58CheckVersion::PKH, Network::Testnet))
+                .unwrap_err(),
+            FromBase58CheckError::UnexpectedVersion(Some((
+                Base58CheckVersion::PKH,
+                Network::Bitcoin
+            )))
+        );
+        assert_eq!(
+            "14cxpo3MBCYYWCgF74SWTdcmxipnGUsPw3"
+                .from_base58check_expect((Base58CheckVersion::SH, Network::Bitcoin))
+                .unwrap_err(),
+            FromBase58CheckError::UnexpectedVersion(Some((
+                Base58CheckVersion::PKH,
+                Network::Bitcoin
+            )))
+        );
+        assert_eq!(
+            "3vQB7B6MrGQZaxCuFg4oh"
+                .from_base58check_expect((Base58CheckVersion::PKH, Network::Bitcoin))
+                .unwrap_err(),
+            FromBase58CheckError::UnexpectedVersion(None)
+        );
+    }
+
+    #[test]
+    fn from_base58_error_non_ascii() {
+        assert_eq!(
+            "2yGé".from_base58().unwrap_err(),
+            FromBase58Error::InvalidCharacter {
+                character: 'é',
+                index: 3
+            }
+        );
+        assert_eq!(
+            "xyz0".from_base58().unwrap_err().to_string(),
+            "invalid base58 character '0' at offset 3"
         );
     }
 }
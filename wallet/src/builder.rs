@@ -0,0 +1,513 @@
+use crate::interpreter::ScriptNum;
+use crate::script::{FromScript, PushBytes, Script, ScriptBuf};
+use tag::FromTag;
+
+impl Script {
+    // The push of `data` that MINIMALDATA accepts: OP_0, OP_1NEGATE and OP_1
+    // to OP_16 for what they stand for, then the smallest push that fits.
+    pub fn push(data: &[u8]) -> Script {
+        match data {
+            [0x81] => Script::OP_1NEGATE,
+            [n @ 0x01..=0x10] => Script::from_tag(0x50 + n),
+            _ => PushBytes::new(data.to_vec()).map_or(Script::OP_FALSE, Script::Push),
+        }
+    }
+
+    // Pushes `n` as a script number, OP_0, OP_1NEGATE and OP_1 to OP_16 being
+    // the shortest way for the numbers they stand for.
+    pub fn push_int(n: i64) -> Script {
+        Self::push(&ScriptNum(n).to_bytes())
+    }
+
+    // Rewrites every push of `script` the way `push` would, which is what
+    // MINIMALDATA accepts, and tells whether `script` already was like that.
+    // Pushes are left as bytes, whether they hold a number with extra zeros
+    // is only known where one is expected.
+    pub fn minimize_pushes(script: &[Script]) -> (Vec<Script>, bool) {
+        let mut was_minimal = true;
+        let script = script
+            .iter()
+            .map(|op| match op {
+                Script::Push(push) => {
+                    let minimal = Self::push(push.data());
+                    was_minimal &= minimal == *op;
+                    minimal
+                }
+                _ => op.clone(),
+            })
+            .collect();
+
+        (script, was_minimal)
+    }
+
+    pub fn p2pk(pubkey: &[u8]) -> Vec<Script> {
+        vec![Self::push(pubkey), Script::OP_CHECKSIG]
+    }
+
+    pub fn p2pkh(pubkey_hash: &[u8; 20]) -> Vec<Script> {
+        vec![
+            Script::OP_DUP,
+            Script::OP_HASH160,
+            Self::push(pubkey_hash),
+            Script::OP_EQUALVERIFY,
+            Script::OP_CHECKSIG,
+        ]
+    }
+
+    pub fn p2sh(script_hash: &[u8; 20]) -> Vec<Script> {
+        vec![
+            Script::OP_HASH160,
+            Self::push(script_hash),
+            Script::OP_EQUAL,
+        ]
+    }
+
+    // The version (0 to 16) followed by the program. Panics on versions that
+    // don't have an opcode.
+    pub fn witness_program(version: u8, program: &[u8]) -> Vec<Script> {
+        assert!(version <= 16, "Witness versions go from 0 to 16.");
+        vec![Self::push_int(version as i64), Self::push(program)]
+    }
+
+    pub fn p2wpkh(pubkey_hash: &[u8; 20]) -> Vec<Script> {
+        Self::witness_program(0, pubkey_hash)
+    }
+
+    pub fn p2wsh(script_hash: &[u8; 32]) -> Vec<Script> {
+        Self::witness_program(0, script_hash)
+    }
+
+    pub fn p2tr(output_key: &[u8; 32]) -> Vec<Script> {
+        Self::witness_program(1, output_key)
+    }
+
+    // `required` of the `keys` must sign. Panics if that's more than there are
+    // keys or more keys than CHECKMULTISIG accepts.
+    pub fn multisig<K: AsRef<[u8]>>(required: usize, keys: &[K]) -> Vec<Script> {
+        assert!(
+            required <= keys.len(),
+            "More signatures required than there are keys."
+        );
+        assert!(keys.len() <= 20, "CHECKMULTISIG accepts up to 20 keys.");
+
+        let mut script = vec![Self::push_int(required as i64)];
+        for key in keys {
+            script.push(Self::push(key.as_ref()));
+        }
+        script.push(Self::push_int(keys.len() as i64));
+        script.push(Script::OP_CHECKMULTISIG);
+
+        script
+    }
+
+    pub fn op_return(data: &[u8]) -> Vec<Script> {
+        vec![Script::OP_RETURN, Self::push(data)]
+    }
+
+    pub fn p2pk_script_sig(signature: &[u8]) -> Vec<Script> {
+        vec![Self::push(signature)]
+    }
+
+    pub fn p2pkh_script_sig(signature: &[u8], pubkey: &[u8]) -> Vec<Script> {
+        vec![Self::push(signature), Self::push(pubkey)]
+    }
+
+    // Signatures must be in the order of their keys. Starts with the dummy
+    // element CHECKMULTISIG pops, empty as NULLDUMMY requires.
+    pub fn multisig_script_sig<S: AsRef<[u8]>>(signatures: &[S]) -> Vec<Script> {
+        let mut script = vec![Script::OP_FALSE];
+        for signature in signatures {
+            script.push(Self::push(signature.as_ref()));
+        }
+
+        script
+    }
+
+    // What spends `redeem_script` followed by the push of the redeem script.
+    // With an empty `script_sig`, this is the scriptSig of P2SH-wrapped
+    // segwit outputs.
+    pub fn p2sh_script_sig(script_sig: &[Script], redeem_script: &[Script]) -> Vec<Script> {
+        let mut script = script_sig.to_vec();
+        script.push(Self::push(&redeem_script.from_script()));
+
+        script
+    }
+
+    pub fn p2wpkh_witness(signature: &[u8], pubkey: &[u8]) -> Vec<Vec<u8>> {
+        vec![signature.to_vec(), pubkey.to_vec()]
+    }
+
+    // The stack `witness_script` starts with, followed by the script itself.
+    // Multisig stacks start with an empty dummy element like scriptSigs do.
+    pub fn p2wsh_witness<I: AsRef<[u8]>>(stack: &[I], witness_script: &[Script]) -> Vec<Vec<u8>> {
+        let mut witness: Vec<Vec<u8>> = stack.iter().map(|item| item.as_ref().to_vec()).collect();
+        witness.push(witness_script.from_script());
+
+        witness
+    }
+
+    pub fn p2tr_key_path_witness(signature: &[u8]) -> Vec<Vec<u8>> {
+        vec![signature.to_vec()]
+    }
+
+    pub fn p2tr_script_path_witness<I: AsRef<[u8]>>(
+        stack: &[I],
+        tapscript: &[Script],
+        control_block: &[u8],
+    ) -> Vec<Vec<u8>> {
+        let mut witness = Self::p2wsh_witness(stack, tapscript);
+        witness.push(control_block.to_vec());
+
+        witness
+    }
+}
+
+// Values that go into a script as a single push: integers as script numbers,
+// bytes as they are. The `script!` macro pushes what it interpolates with it.
+pub trait ToPush {
+    fn to_push(&self) -> Script;
+}
+
+macro_rules! impl_to_push_for_int {
+    ($($int:ty),*) => {
+        $(impl ToPush for $int {
+            fn to_push(&self) -> Script {
+                Script::push_int(*self as i64)
+            }
+        })*
+    };
+}
+
+impl_to_push_for_int!(i8, i16, i32, i64, u8, u16, u32);
+
+macro_rules! impl_to_push_for_bytes {
+    ($($bytes:ty),*) => {
+        $(impl ToPush for $bytes {
+            fn to_push(&self) -> Script {
+                Script::push(self.as_ref())
+            }
+        })*
+    };
+}
+
+impl_to_push_for_bytes!([u8], Vec<u8>, str, String);
+
+impl<const N: usize> ToPush for [u8; N] {
+    fn to_push(&self) -> Script {
+        Script::push(self)
+    }
+}
+
+// A serialized script, like a P2SH redeem script
+impl ToPush for ScriptBuf {
+    fn to_push(&self) -> Script {
+        Script::push(self.as_bytes())
+    }
+}
+
+impl<T: ToPush + ?Sized> ToPush for &T {
+    fn to_push(&self) -> Script {
+        (**self).to_push()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{
+        eval_script, verify_script, BaseSignatureChecker, SigVersion, SignatureChecker, VerifyFlags,
+    };
+    use crate::script::ToScript;
+    use crate::solver::{solve, TxoutType};
+    use crypto::digest::Digest;
+    use crypto::ripemd160::Ripemd160;
+    use crypto::sha2::Sha256;
+
+    const PUBKEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    // Accepts any non-empty signature
+    struct AnySignature;
+
+    impl SignatureChecker for AnySignature {
+        fn check_sig(&self, signature: &[u8], _: &[u8], _: &[Script], _: SigVersion) -> bool {
+            !signature.is_empty()
+        }
+    }
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        let mut result = [0; 32];
+        let mut hasher = Sha256::new();
+        hasher.input(data);
+        hasher.result(&mut result);
+        result
+    }
+
+    fn hash160(data: &[u8]) -> [u8; 20] {
+        let mut result = [0; 20];
+        let mut hasher = Ripemd160::new();
+        hasher.input(&sha256(data));
+        hasher.result(&mut result);
+        result
+    }
+
+    fn bytes(hex_str: &str) -> Vec<u8> {
+        hex::decode(hex_str).unwrap()
+    }
+
+    // DER with 32-byte R and S, then SIGHASH_ALL
+    fn signature() -> Vec<u8> {
+        let mut signature = vec![0x30, 0x44];
+        for _ in 0..2 {
+            signature.extend([0x02, 0x20]);
+            signature.extend([0x01; 32]);
+        }
+        signature.push(0x01);
+        signature
+    }
+
+    fn direct(data: &[u8]) -> Script {
+        Script::Push(PushBytes::new(data.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn push() {
+        assert_eq!(Script::push(&[]), Script::OP_FALSE);
+        assert_eq!(Script::push(&[0x81]), Script::OP_1NEGATE);
+        assert_eq!(Script::push(&[0x01]), Script::OP_TRUE);
+        assert_eq!(Script::push(&[0x10]), Script::OP_16);
+        assert_eq!(Script::push(&[0x00]), direct(&[0x00]));
+        assert_eq!(Script::push(&[0x11; 0x4b]), direct(&[0x11; 0x4b]));
+        assert_eq!(
+            [Script::push(&[0x11; 0x4c])].from_script()[..2],
+            [0x4c, 0x4c]
+        );
+        assert_eq!(
+            [Script::push(&[0x11; 0x100])].from_script()[..3],
+            [0x4d, 0x00, 0x01]
+        );
+        assert_eq!(
+            [Script::push(&[0x11; 0x10000])].from_script()[..5],
+            [0x4e, 0x00, 0x00, 0x01, 0x00]
+        );
+
+        assert_eq!(Script::push_int(0), Script::OP_FALSE);
+        assert_eq!(Script::push_int(-1), Script::OP_1NEGATE);
+        assert_eq!(Script::push_int(16), Script::OP_16);
+        assert_eq!(Script::push_int(17), direct(&[0x11]));
+        assert_eq!(Script::push_int(-2), direct(&[0x82]));
+    }
+
+    #[test]
+    fn minimize_pushes() {
+        let minimal = |hex_script: &str| {
+            let (script, was_minimal) =
+                Script::minimize_pushes(&hex::decode(hex_script).unwrap().to_script().unwrap());
+            (hex::encode(script.from_script()), was_minimal)
+        };
+
+        assert_eq!(minimal(""), ("".to_string(), true));
+        assert_eq!(
+            minimal("76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac"),
+            (
+                "76a91489abcdefabbaabbaabbaabbaabbaabbaabbaabba88ac".to_string(),
+                true
+            )
+        );
+        assert_eq!(
+            minimal("004f516001110182"),
+            ("004f516001110182".to_string(), true)
+        );
+        // numbers pushed as data, and OP_PUSHDATAx for what fits in less
+        assert_eq!(minimal("0100"), ("0100".to_string(), true));
+        assert_eq!(minimal("0107"), ("57".to_string(), false));
+        assert_eq!(minimal("0181"), ("4f".to_string(), false));
+        assert_eq!(minimal("4c00"), ("00".to_string(), false));
+        assert_eq!(minimal("4c0107"), ("57".to_string(), false));
+        assert_eq!(minimal("4c0111"), ("0111".to_string(), false));
+        assert_eq!(minimal("4d0200ffff75"), ("02ffff75".to_string(), false));
+        assert_eq!(
+            minimal(&format!("4e00010000{}", "11".repeat(0x100))),
+            (format!("4d0001{}", "11".repeat(0x100)), false)
+        );
+        // a number with extra zeros is still a minimal push
+        assert_eq!(minimal("020100"), ("020100".to_string(), true));
+
+        // agrees with the interpreter
+        for hex_script in ["0111", "0107", "4c0111", "4c01ff", "4d0200ffff"] {
+            let script = hex::decode(hex_script).unwrap().to_script().unwrap();
+            let mut stack = Vec::new();
+            let result = eval_script(
+                &mut stack,
+                &script,
+                VerifyFlags::MINIMALDATA,
+                SigVersion::Base,
+                &BaseSignatureChecker,
+            );
+            assert_eq!(
+                result.is_ok(),
+                Script::minimize_pushes(&script).1,
+                "{}",
+                hex_script
+            );
+        }
+    }
+
+    #[test]
+    fn to_push() {
+        assert_eq!(0.to_push(), Script::OP_FALSE);
+        assert_eq!((-1i8).to_push(), Script::OP_1NEGATE);
+        assert_eq!(1000u16.to_push(), direct(&[0xe8, 0x03]));
+        assert_eq!(u32::MAX.to_push(), direct(&[0xff, 0xff, 0xff, 0xff, 0x00]));
+        assert_eq!([0x11; 20].to_push(), direct(&[0x11; 20]));
+        assert_eq!(vec![0x01].to_push(), Script::OP_TRUE);
+        assert_eq!((&&[0x11, 0x22][..]).to_push(), direct(&[0x11, 0x22]));
+        assert_eq!("abc".to_push(), direct(b"abc"));
+        assert_eq!(String::new().to_push(), Script::OP_FALSE);
+        assert_eq!(
+            ScriptBuf::from(Script::p2pk(&bytes(PUBKEY)).as_slice()).to_push(),
+            direct(&[&[0x21][..], &bytes(PUBKEY), &[0xac]].concat())
+        );
+    }
+
+    #[test]
+    fn script_pubkeys() {
+        let pubkey = bytes(PUBKEY);
+        let hash = hash160(&pubkey);
+
+        assert_eq!(
+            Script::p2pkh(&hash).from_script(),
+            [&[0x76, 0xa9, 0x14][..], &hash, &[0x88, 0xac]].concat()
+        );
+        assert_eq!(
+            Script::p2sh(&hash).from_script(),
+            [&[0xa9, 0x14][..], &hash, &[0x87]].concat()
+        );
+        assert_eq!(
+            Script::p2wsh(&[0x01; 32]).from_script(),
+            [&[0x00, 0x20][..], &[0x01; 32]].concat()
+        );
+        assert_eq!(
+            Script::witness_program(16, &[0x01, 0x02]).from_script(),
+            vec![0x60, 0x02, 0x01, 0x02]
+        );
+
+        assert_eq!(
+            solve(&Script::p2pk(&pubkey)),
+            TxoutType::PubKey(pubkey.clone())
+        );
+        assert_eq!(solve(&Script::p2pkh(&hash)), TxoutType::PubKeyHash(hash));
+        assert_eq!(solve(&Script::p2sh(&hash)), TxoutType::ScriptHash(hash));
+        assert_eq!(
+            solve(&Script::p2wpkh(&hash)),
+            TxoutType::WitnessV0KeyHash(hash)
+        );
+        assert_eq!(
+            solve(&Script::p2wsh(&[0x01; 32])),
+            TxoutType::WitnessV0ScriptHash([0x01; 32])
+        );
+        assert_eq!(
+            solve(&Script::p2tr(&[0x01; 32])),
+            TxoutType::WitnessV1Taproot([0x01; 32])
+        );
+        assert_eq!(
+            solve(&Script::multisig(1, &[&pubkey, &pubkey])),
+            TxoutType::Multisig {
+                required: 1,
+                keys: vec![pubkey.clone(), pubkey.clone()]
+            }
+        );
+        assert_eq!(solve(&Script::op_return(&[0x11; 80])), TxoutType::NullData);
+
+        // 17 to 20 keys are pushed as numbers
+        let keys = vec![pubkey; 20];
+        let script = Script::multisig(17, &keys);
+        assert_eq!(script[0], direct(&[17]));
+        assert_eq!(script[21], direct(&[20]));
+    }
+
+    #[test]
+    #[should_panic(expected = "More signatures required than there are keys.")]
+    fn multisig_with_too_few_keys() {
+        Script::multisig(2, &[bytes(PUBKEY)]);
+    }
+
+    #[test]
+    fn spends() {
+        let pubkey = bytes(PUBKEY);
+        let signature = signature();
+        let flags = VerifyFlags::STANDARD;
+
+        let check = |script_sig: &[Script], script_pubkey: &[Script], witness: &[Vec<u8>]| {
+            verify_script(script_sig, script_pubkey, witness, flags, &AnySignature)
+        };
+
+        assert_eq!(
+            check(
+                &Script::p2pk_script_sig(&signature),
+                &Script::p2pk(&pubkey),
+                &[]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                &Script::p2pkh_script_sig(&signature, &pubkey),
+                &Script::p2pkh(&hash160(&pubkey)),
+                &[]
+            ),
+            Ok(())
+        );
+
+        let multisig = Script::multisig(2, &[&pubkey, &pubkey, &pubkey]);
+        let multisig_script_sig = Script::multisig_script_sig(&[&signature, &signature]);
+        assert_eq!(check(&multisig_script_sig, &multisig, &[]), Ok(()));
+
+        let redeem_script_hash = hash160(&multisig.from_script());
+        assert_eq!(
+            check(
+                &Script::p2sh_script_sig(&multisig_script_sig, &multisig),
+                &Script::p2sh(&redeem_script_hash),
+                &[]
+            ),
+            Ok(())
+        );
+
+        let p2wpkh = Script::p2wpkh(&hash160(&pubkey));
+        let p2wpkh_witness = Script::p2wpkh_witness(&signature, &pubkey);
+        assert_eq!(check(&[], &p2wpkh, &p2wpkh_witness), Ok(()));
+        assert_eq!(
+            check(
+                &Script::p2sh_script_sig(&[], &p2wpkh),
+                &Script::p2sh(&hash160(&p2wpkh.from_script())),
+                &p2wpkh_witness
+            ),
+            Ok(())
+        );
+
+        let p2wsh = Script::p2wsh(&sha256(&multisig.from_script()));
+        let p2wsh_witness = Script::p2wsh_witness(&[&[][..], &signature, &signature], &multisig);
+        assert_eq!(check(&[], &p2wsh, &p2wsh_witness), Ok(()));
+    }
+
+    #[test]
+    fn taproot_witnesses() {
+        let tapscript = vec![Script::OP_TRUE];
+        let control_block = [vec![0xc0], vec![0x01; 32]].concat();
+
+        assert_eq!(
+            Script::p2tr_key_path_witness(&[0x01; 64]),
+            vec![vec![0x01; 64]]
+        );
+        assert_eq!(
+            Script::p2tr_script_path_witness(&[[0x02]], &tapscript, &control_block),
+            vec![vec![0x02], vec![0x51], control_block.clone()]
+        );
+
+        let hash = sha256(&[0x51]);
+        assert_eq!(
+            Script::p2wsh_witness::<&[u8]>(&[], &tapscript),
+            vec![vec![0x51]]
+        );
+        assert_eq!(Script::p2wsh(&hash)[1], direct(&hash));
+    }
+}
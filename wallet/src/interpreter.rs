@@ -0,0 +1,2895 @@
+// Evaluation of scripts, following what Bitcoin Core does in
+// https://github.com/bitcoin/bitcoin/blob/master/src/script/interpreter.cpp
+// Checking signatures and the taproot tweak is left to a `SignatureChecker`,
+// since it depends on the spending transaction and on curve arithmetic.
+
+use crate::script::{FromScript, Instructions, PushBytes, Script};
+use crypto::{digest::Digest, ripemd160::Ripemd160, sha1::Sha1, sha2::Sha256};
+use std::{error, fmt, ops, slice, str::FromStr};
+
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+pub const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+pub const MAX_PUBKEYS_PER_MULTISIG: i32 = 20;
+// counts both the main and the alt stack
+pub const MAX_STACK_SIZE: usize = 1000;
+
+// every signature checked in tapscript uses this much of the budget, which
+// starts at the size of the witness plus the same amount
+const VALIDATION_WEIGHT_PER_SIGOP_PASSED: i64 = 50;
+const TAPROOT_LEAF_TAPSCRIPT: u8 = 0xc0;
+const TAPROOT_CONTROL_BASE_SIZE: usize = 33;
+const TAPROOT_CONTROL_NODE_SIZE: usize = 32;
+const TAPROOT_CONTROL_MAX_NODE_COUNT: usize = 128;
+const ANNEX_TAG: u8 = 0x50;
+const SEQUENCE_LOCKTIME_DISABLE_FLAG: i64 = 1 << 31;
+
+pub type Stack = Vec<Vec<u8>>;
+
+// Script verification flags, with the same names as in Core. Consensus only
+// needs `CONSENSUS`, the rest are policy rules that Core enforces before
+// relaying or mining a transaction.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct VerifyFlags(u32);
+
+impl VerifyFlags {
+    pub const NONE: VerifyFlags = VerifyFlags(0);
+    pub const P2SH: VerifyFlags = VerifyFlags(1 << 0);
+    pub const STRICTENC: VerifyFlags = VerifyFlags(1 << 1);
+    pub const DERSIG: VerifyFlags = VerifyFlags(1 << 2);
+    pub const LOW_S: VerifyFlags = VerifyFlags(1 << 3);
+    pub const NULLDUMMY: VerifyFlags = VerifyFlags(1 << 4);
+    pub const SIGPUSHONLY: VerifyFlags = VerifyFlags(1 << 5);
+    pub const MINIMALDATA: VerifyFlags = VerifyFlags(1 << 6);
+    pub const DISCOURAGE_UPGRADABLE_NOPS: VerifyFlags = VerifyFlags(1 << 7);
+    pub const CLEANSTACK: VerifyFlags = VerifyFlags(1 << 8);
+    pub const CHECKLOCKTIMEVERIFY: VerifyFlags = VerifyFlags(1 << 9);
+    pub const CHECKSEQUENCEVERIFY: VerifyFlags = VerifyFlags(1 << 10);
+    pub const WITNESS: VerifyFlags = VerifyFlags(1 << 11);
+    pub const DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM: VerifyFlags = VerifyFlags(1 << 12);
+    pub const MINIMALIF: VerifyFlags = VerifyFlags(1 << 13);
+    pub const NULLFAIL: VerifyFlags = VerifyFlags(1 << 14);
+    pub const WITNESS_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 15);
+    pub const CONST_SCRIPTCODE: VerifyFlags = VerifyFlags(1 << 16);
+    pub const TAPROOT: VerifyFlags = VerifyFlags(1 << 17);
+    pub const DISCOURAGE_UPGRADABLE_TAPROOT_VERSION: VerifyFlags = VerifyFlags(1 << 18);
+    pub const DISCOURAGE_OP_SUCCESS: VerifyFlags = VerifyFlags(1 << 19);
+    pub const DISCOURAGE_UPGRADABLE_PUBKEYTYPE: VerifyFlags = VerifyFlags(1 << 20);
+
+    // every soft fork up to taproot
+    pub const CONSENSUS: VerifyFlags = VerifyFlags::P2SH
+        .union(VerifyFlags::DERSIG)
+        .union(VerifyFlags::NULLDUMMY)
+        .union(VerifyFlags::CHECKLOCKTIMEVERIFY)
+        .union(VerifyFlags::CHECKSEQUENCEVERIFY)
+        .union(VerifyFlags::WITNESS)
+        .union(VerifyFlags::TAPROOT);
+
+    // STANDARD_SCRIPT_VERIFY_FLAGS in Core
+    pub const STANDARD: VerifyFlags = VerifyFlags::CONSENSUS
+        .union(VerifyFlags::STRICTENC)
+        .union(VerifyFlags::MINIMALDATA)
+        .union(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS)
+        .union(VerifyFlags::CLEANSTACK)
+        .union(VerifyFlags::MINIMALIF)
+        .union(VerifyFlags::NULLFAIL)
+        .union(VerifyFlags::LOW_S)
+        .union(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM)
+        .union(VerifyFlags::WITNESS_PUBKEYTYPE)
+        .union(VerifyFlags::CONST_SCRIPTCODE)
+        .union(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION)
+        .union(VerifyFlags::DISCOURAGE_OP_SUCCESS)
+        .union(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE);
+
+    const NAMES: [(&'static str, VerifyFlags); 21] = [
+        ("P2SH", VerifyFlags::P2SH),
+        ("STRICTENC", VerifyFlags::STRICTENC),
+        ("DERSIG", VerifyFlags::DERSIG),
+        ("LOW_S", VerifyFlags::LOW_S),
+        ("NULLDUMMY", VerifyFlags::NULLDUMMY),
+        ("SIGPUSHONLY", VerifyFlags::SIGPUSHONLY),
+        ("MINIMALDATA", VerifyFlags::MINIMALDATA),
+        (
+            "DISCOURAGE_UPGRADABLE_NOPS",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS,
+        ),
+        ("CLEANSTACK", VerifyFlags::CLEANSTACK),
+        ("CHECKLOCKTIMEVERIFY", VerifyFlags::CHECKLOCKTIMEVERIFY),
+        ("CHECKSEQUENCEVERIFY", VerifyFlags::CHECKSEQUENCEVERIFY),
+        ("WITNESS", VerifyFlags::WITNESS),
+        (
+            "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+        ),
+        ("MINIMALIF", VerifyFlags::MINIMALIF),
+        ("NULLFAIL", VerifyFlags::NULLFAIL),
+        ("WITNESS_PUBKEYTYPE", VerifyFlags::WITNESS_PUBKEYTYPE),
+        ("CONST_SCRIPTCODE", VerifyFlags::CONST_SCRIPTCODE),
+        ("TAPROOT", VerifyFlags::TAPROOT),
+        (
+            "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION,
+        ),
+        ("DISCOURAGE_OP_SUCCESS", VerifyFlags::DISCOURAGE_OP_SUCCESS),
+        (
+            "DISCOURAGE_UPGRADABLE_PUBKEYTYPE",
+            VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE,
+        ),
+    ];
+
+    pub const fn union(self, other: VerifyFlags) -> VerifyFlags {
+        VerifyFlags(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: VerifyFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // whether any of the flags in `other` is set
+    pub const fn intersects(self, other: VerifyFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+}
+
+impl ops::BitOr for VerifyFlags {
+    type Output = VerifyFlags;
+
+    fn bitor(self, other: VerifyFlags) -> VerifyFlags {
+        self.union(other)
+    }
+}
+
+impl ops::BitOrAssign for VerifyFlags {
+    fn bitor_assign(&mut self, other: VerifyFlags) {
+        *self = self.union(other);
+    }
+}
+
+// Comma separated names, as in Core's test data. "NONE" or an empty string
+// are no flags at all.
+impl fmt::Display for VerifyFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = VerifyFlags::NAMES
+            .iter()
+            .filter(|(_, flag)| self.contains(*flag))
+            .map(|(name, _)| *name)
+            .collect();
+
+        if names.is_empty() {
+            write!(f, "NONE")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseVerifyFlagsError {
+    pub flag: String,
+}
+
+impl fmt::Display for ParseVerifyFlagsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown script verification flag {:?}", self.flag)
+    }
+}
+
+impl error::Error for ParseVerifyFlagsError {}
+
+impl FromStr for VerifyFlags {
+    type Err = ParseVerifyFlagsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut flags = VerifyFlags::NONE;
+
+        for name in s.split(',').map(str::trim) {
+            if name.is_empty() || name == "NONE" {
+                continue;
+            }
+
+            let (_, flag) = VerifyFlags::NAMES
+                .iter()
+                .find(|(flag_name, _)| *flag_name == name)
+                .ok_or_else(|| ParseVerifyFlagsError {
+                    flag: name.to_string(),
+                })?;
+            flags |= *flag;
+        }
+
+        Ok(flags)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScriptError {
+    // Core throws an exception for numbers that are too long or not minimally
+    // encoded and reports it as an unknown error
+    UnknownError,
+    EvalFalse,
+    OpReturn,
+
+    ScriptSize,
+    PushSize,
+    OpCount,
+    StackSize,
+    SigCount,
+    PubkeyCount,
+
+    Verify,
+    EqualVerify,
+    CheckMultisigVerify,
+    CheckSigVerify,
+    NumEqualVerify,
+
+    BadOpcode,
+    DisabledOpcode,
+    InvalidStackOperation,
+    InvalidAltstackOperation,
+    UnbalancedConditional,
+
+    NegativeLocktime,
+    UnsatisfiedLocktime,
+
+    SigHashtype,
+    SigDer,
+    MinimalData,
+    SigPushOnly,
+    SigHighS,
+    SigNullDummy,
+    PubkeyType,
+    CleanStack,
+    MinimalIf,
+    SigNullFail,
+
+    DiscourageUpgradableNops,
+    DiscourageUpgradableWitnessProgram,
+    DiscourageUpgradableTaprootVersion,
+    DiscourageOpSuccess,
+    DiscourageUpgradablePubkeyType,
+
+    WitnessProgramWrongLength,
+    WitnessProgramWitnessEmpty,
+    WitnessProgramMismatch,
+    WitnessMalleated,
+    WitnessMalleatedP2sh,
+    WitnessUnexpected,
+    WitnessPubkeyType,
+
+    SchnorrSigSize,
+    SchnorrSigHashtype,
+    SchnorrSig,
+    TaprootWrongControlSize,
+    TapscriptValidationWeight,
+    TapscriptCheckMultisig,
+    TapscriptMinimalIf,
+    TapscriptEmptyPubkey,
+
+    OpCodeSeparator,
+    SigFindAndDelete,
+}
+
+impl ScriptError {
+    // The name Core uses for the error in its test data, like "EVAL_FALSE".
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScriptError::UnknownError => "UNKNOWN_ERROR",
+            ScriptError::EvalFalse => "EVAL_FALSE",
+            ScriptError::OpReturn => "OP_RETURN",
+            ScriptError::ScriptSize => "SCRIPT_SIZE",
+            ScriptError::PushSize => "PUSH_SIZE",
+            ScriptError::OpCount => "OP_COUNT",
+            ScriptError::StackSize => "STACK_SIZE",
+            ScriptError::SigCount => "SIG_COUNT",
+            ScriptError::PubkeyCount => "PUBKEY_COUNT",
+            ScriptError::Verify => "VERIFY",
+            ScriptError::EqualVerify => "EQUALVERIFY",
+            ScriptError::CheckMultisigVerify => "CHECKMULTISIGVERIFY",
+            ScriptError::CheckSigVerify => "CHECKSIGVERIFY",
+            ScriptError::NumEqualVerify => "NUMEQUALVERIFY",
+            ScriptError::BadOpcode => "BAD_OPCODE",
+            ScriptError::DisabledOpcode => "DISABLED_OPCODE",
+            ScriptError::InvalidStackOperation => "INVALID_STACK_OPERATION",
+            ScriptError::InvalidAltstackOperation => "INVALID_ALTSTACK_OPERATION",
+            ScriptError::UnbalancedConditional => "UNBALANCED_CONDITIONAL",
+            ScriptError::NegativeLocktime => "NEGATIVE_LOCKTIME",
+            ScriptError::UnsatisfiedLocktime => "UNSATISFIED_LOCKTIME",
+            ScriptError::SigHashtype => "SIG_HASHTYPE",
+            ScriptError::SigDer => "SIG_DER",
+            ScriptError::MinimalData => "MINIMALDATA",
+            ScriptError::SigPushOnly => "SIG_PUSHONLY",
+            ScriptError::SigHighS => "SIG_HIGH_S",
+            ScriptError::SigNullDummy => "SIG_NULLDUMMY",
+            ScriptError::PubkeyType => "PUBKEYTYPE",
+            ScriptError::CleanStack => "CLEANSTACK",
+            ScriptError::MinimalIf => "MINIMALIF",
+            ScriptError::SigNullFail => "NULLFAIL",
+            ScriptError::DiscourageUpgradableNops => "DISCOURAGE_UPGRADABLE_NOPS",
+            ScriptError::DiscourageUpgradableWitnessProgram => {
+                "DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM"
+            }
+            ScriptError::DiscourageUpgradableTaprootVersion => {
+                "DISCOURAGE_UPGRADABLE_TAPROOT_VERSION"
+            }
+            ScriptError::DiscourageOpSuccess => "DISCOURAGE_OP_SUCCESS",
+            ScriptError::DiscourageUpgradablePubkeyType => "DISCOURAGE_UPGRADABLE_PUBKEYTYPE",
+            ScriptError::WitnessProgramWrongLength => "WITNESS_PROGRAM_WRONG_LENGTH",
+            ScriptError::WitnessProgramWitnessEmpty => "WITNESS_PROGRAM_WITNESS_EMPTY",
+            ScriptError::WitnessProgramMismatch => "WITNESS_PROGRAM_MISMATCH",
+            ScriptError::WitnessMalleated => "WITNESS_MALLEATED",
+            ScriptError::WitnessMalleatedP2sh => "WITNESS_MALLEATED_P2SH",
+            ScriptError::WitnessUnexpected => "WITNESS_UNEXPECTED",
+            ScriptError::WitnessPubkeyType => "WITNESS_PUBKEYTYPE",
+            ScriptError::SchnorrSigSize => "SCHNORR_SIG_SIZE",
+            ScriptError::SchnorrSigHashtype => "SCHNORR_SIG_HASHTYPE",
+            ScriptError::SchnorrSig => "SCHNORR_SIG",
+            ScriptError::TaprootWrongControlSize => "TAPROOT_WRONG_CONTROL_SIZE",
+            ScriptError::TapscriptValidationWeight => "TAPSCRIPT_VALIDATION_WEIGHT",
+            ScriptError::TapscriptCheckMultisig => "TAPSCRIPT_CHECKMULTISIG",
+            ScriptError::TapscriptMinimalIf => "TAPSCRIPT_MINIMALIF",
+            ScriptError::TapscriptEmptyPubkey => "TAPSCRIPT_EMPTY_PUBKEY",
+            ScriptError::OpCodeSeparator => "OP_CODESEPARATOR",
+            ScriptError::SigFindAndDelete => "SIG_FINDANDDELETE",
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match self {
+            ScriptError::UnknownError => "unknown error",
+            ScriptError::EvalFalse => {
+                "Script evaluated without error but finished with a false/empty top stack element"
+            }
+            ScriptError::OpReturn => "OP_RETURN was encountered",
+            ScriptError::ScriptSize => "Script is too big",
+            ScriptError::PushSize => "Push value size limit exceeded",
+            ScriptError::OpCount => "Operation limit exceeded",
+            ScriptError::StackSize => "Stack size limit exceeded",
+            ScriptError::SigCount => "Signature count negative or greater than pubkey count",
+            ScriptError::PubkeyCount => "Pubkey count negative or limit exceeded",
+            ScriptError::Verify => "Script failed an OP_VERIFY operation",
+            ScriptError::EqualVerify => "Script failed an OP_EQUALVERIFY operation",
+            ScriptError::CheckMultisigVerify => "Script failed an OP_CHECKMULTISIGVERIFY operation",
+            ScriptError::CheckSigVerify => "Script failed an OP_CHECKSIGVERIFY operation",
+            ScriptError::NumEqualVerify => "Script failed an OP_NUMEQUALVERIFY operation",
+            ScriptError::BadOpcode => "Opcode missing or not understood",
+            ScriptError::DisabledOpcode => "Attempted to use a disabled opcode",
+            ScriptError::InvalidStackOperation => "Operation not valid with the current stack size",
+            ScriptError::InvalidAltstackOperation => {
+                "Operation not valid with the current altstack size"
+            }
+            ScriptError::UnbalancedConditional => "Invalid OP_IF construction",
+            ScriptError::NegativeLocktime => "Negative locktime",
+            ScriptError::UnsatisfiedLocktime => "Locktime requirement not satisfied",
+            ScriptError::SigHashtype => "Signature hash type missing or not understood",
+            ScriptError::SigDer => "Non-canonical DER signature",
+            ScriptError::MinimalData => "Data push larger than necessary",
+            ScriptError::SigPushOnly => "Only push operators allowed in signatures",
+            ScriptError::SigHighS => "Non-canonical signature: S value is unnecessarily high",
+            ScriptError::SigNullDummy => "Dummy CHECKMULTISIG argument must be zero",
+            ScriptError::PubkeyType => "Public key is neither compressed or uncompressed",
+            ScriptError::CleanStack => "Stack size must be exactly one after execution",
+            ScriptError::MinimalIf => "OP_IF/NOTIF argument must be minimal",
+            ScriptError::SigNullFail => {
+                "Signature must be zero for failed CHECK(MULTI)SIG operation"
+            }
+            ScriptError::DiscourageUpgradableNops => "NOPx reserved for soft-fork upgrades",
+            ScriptError::DiscourageUpgradableWitnessProgram => {
+                "Witness version reserved for soft-fork upgrades"
+            }
+            ScriptError::DiscourageUpgradableTaprootVersion => {
+                "Taproot version reserved for soft-fork upgrades"
+            }
+            ScriptError::DiscourageOpSuccess => "OP_SUCCESSx reserved for soft-fork upgrades",
+            ScriptError::DiscourageUpgradablePubkeyType => {
+                "Public key version reserved for soft-fork upgrades"
+            }
+            ScriptError::WitnessProgramWrongLength => "Witness program has incorrect length",
+            ScriptError::WitnessProgramWitnessEmpty => {
+                "Witness program was passed an empty witness"
+            }
+            ScriptError::WitnessProgramMismatch => "Witness program hash mismatch",
+            ScriptError::WitnessMalleated => "Witness requires empty scriptSig",
+            ScriptError::WitnessMalleatedP2sh => "Witness requires only-redeemscript scriptSig",
+            ScriptError::WitnessUnexpected => "Witness provided for non-witness script",
+            ScriptError::WitnessPubkeyType => "Using non-compressed keys in segwit",
+            ScriptError::SchnorrSigSize => "Invalid Schnorr signature size",
+            ScriptError::SchnorrSigHashtype => "Invalid Schnorr signature hash type",
+            ScriptError::SchnorrSig => "Invalid Schnorr signature",
+            ScriptError::TaprootWrongControlSize => "Invalid Taproot control block size",
+            ScriptError::TapscriptValidationWeight => {
+                "Too much signature validation relative to witness weight"
+            }
+            ScriptError::TapscriptCheckMultisig => {
+                "OP_CHECKMULTISIG(VERIFY) is not available in tapscript"
+            }
+            ScriptError::TapscriptMinimalIf => "OP_IF/NOTIF argument must be minimal in tapscript",
+            ScriptError::TapscriptEmptyPubkey => "Empty public key in tapscript",
+            ScriptError::OpCodeSeparator => "Using OP_CODESEPARATOR in non-witness script",
+            ScriptError::SigFindAndDelete => "Signature is found in scriptCode",
+        };
+
+        write!(f, "{}", message)
+    }
+}
+
+impl error::Error for ScriptError {}
+
+// Numbers on the stack are little-endian with the sign in the most significant
+// bit of the last byte, see CScriptNum in Core.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ScriptNum(pub i64);
+
+impl ScriptNum {
+    // operands of arithmetic opcodes can't be longer than this, but results
+    // can (and are still pushed)
+    pub const DEFAULT_MAX_SIZE: usize = 4;
+
+    // `require_minimal` rejects encodings with needless zero bytes, like
+    // MINIMALDATA does
+    pub fn from_bytes(
+        bytes: &[u8],
+        require_minimal: bool,
+        max_size: usize,
+    ) -> Result<ScriptNum, ScriptError> {
+        if bytes.len() > max_size {
+            return Err(ScriptError::UnknownError);
+        }
+
+        let last = match bytes.last() {
+            Some(last) => *last,
+            None => return Ok(ScriptNum(0)),
+        };
+
+        // the last byte can only be 0x00 or 0x80 if the one before it needs
+        // its most significant bit
+        if require_minimal
+            && last & 0x7f == 0
+            && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0)
+        {
+            return Err(ScriptError::UnknownError);
+        }
+
+        let mut value: i64 = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            value |= (*byte as i64) << (8 * i);
+        }
+
+        if last & 0x80 != 0 {
+            let sign_bit = 0x80 << (8 * (bytes.len() - 1));
+            Ok(ScriptNum(-(value & !sign_bit)))
+        } else {
+            Ok(ScriptNum(value))
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        let mut abs = self.0.unsigned_abs();
+        while abs > 0 {
+            bytes.push((abs & 0xff) as u8);
+            abs >>= 8;
+        }
+
+        // the sign goes in an extra byte when the last one already uses the
+        // most significant bit
+        if let Some(last) = bytes.last_mut() {
+            if *last & 0x80 != 0 {
+                bytes.push(if self.0 < 0 { 0x80 } else { 0x00 });
+            } else if self.0 < 0 {
+                *last |= 0x80;
+            }
+        }
+
+        bytes
+    }
+
+    // Same as `getint()` in Core
+    fn to_i32(self) -> i32 {
+        self.0.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+pub fn cast_to_bool(bytes: &[u8]) -> bool {
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte != 0 {
+            // negative zero is false
+            return !(i == bytes.len() - 1 && *byte == 0x80);
+        }
+    }
+
+    false
+}
+
+// What signatures commit to depends on where the script being run came from.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SigVersion {
+    Base,
+    WitnessV0,
+    // taproot key path spends
+    Taproot,
+    Tapscript,
+}
+
+// Data from the taproot witness that schnorr signatures commit to.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExecData {
+    pub tapleaf_hash: Option<[u8; 32]>,
+    // counted in opcodes, u32::MAX when no OP_CODESEPARATOR was executed
+    pub codeseparator_pos: u32,
+    // the annex without its 0x50 tag
+    pub annex: Option<Vec<u8>>,
+    validation_weight_left: i64,
+}
+
+impl Default for ExecData {
+    fn default() -> Self {
+        ExecData {
+            tapleaf_hash: None,
+            codeseparator_pos: u32::MAX,
+            annex: None,
+            validation_weight_left: 0,
+        }
+    }
+}
+
+pub trait SignatureChecker {
+    // Checks an ECDSA signature, which is never empty and still has its
+    // sighash type as the last byte. `script_code` is what the signature
+    // commits to: the script after the last executed OP_CODESEPARATOR
+    // (without the signature itself, for `SigVersion::Base`).
+    fn check_sig(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &[Script],
+        sig_version: SigVersion,
+    ) -> bool;
+
+    // `signature` is 64 bytes, or 65 with a valid sighash type, and `pubkey`
+    // is a 32 bytes x-only key.
+    fn check_schnorr_sig(
+        &self,
+        _signature: &[u8],
+        _pubkey: &[u8],
+        _sig_version: SigVersion,
+        _exec_data: &ExecData,
+    ) -> bool {
+        false
+    }
+
+    // Whether `output_key` is `internal_key` tweaked with `merkle_root` and has
+    // an odd Y if `parity` is set, see CheckTapTweak in Core. Both keys are
+    // 32 bytes x-only keys.
+    fn check_tap_tweak(
+        &self,
+        _internal_key: &[u8],
+        _output_key: &[u8],
+        _merkle_root: &[u8; 32],
+        _parity: bool,
+    ) -> bool {
+        false
+    }
+
+    // Whether the spending transaction satisfies OP_CHECKLOCKTIMEVERIFY
+    fn check_lock_time(&self, _lock_time: i64) -> bool {
+        false
+    }
+
+    // Whether the spending input satisfies OP_CHECKSEQUENCEVERIFY
+    fn check_sequence(&self, _sequence: i64) -> bool {
+        false
+    }
+}
+
+// Fails every check, like BaseSignatureChecker in Core. Useful for scripts that
+// shouldn't need any.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BaseSignatureChecker;
+
+impl SignatureChecker for BaseSignatureChecker {
+    fn check_sig(
+        &self,
+        _signature: &[u8],
+        _pubkey: &[u8],
+        _script_code: &[Script],
+        _sig_version: SigVersion,
+    ) -> bool {
+        false
+    }
+}
+
+fn hash<D: Digest>(mut hasher: D, data: &[u8]) -> Vec<u8> {
+    let mut result = vec![0; hasher.output_bytes()];
+    hasher.input(data);
+    hasher.result(&mut result);
+
+    result
+}
+
+// BIP340 tagged hash: sha256(sha256(tag) || sha256(tag) || data)
+fn tagged_hash(tag: &str, data: &[u8]) -> [u8; 32] {
+    let tag_hash = hash(Sha256::new(), tag.as_bytes());
+
+    let mut result = [0; 32];
+    let mut hasher = Sha256::new();
+    hasher.input(&tag_hash);
+    hasher.input(&tag_hash);
+    hasher.input(data);
+    hasher.result(&mut result);
+
+    result
+}
+
+fn compact_size(size: usize) -> Vec<u8> {
+    match size {
+        0..=0xfc => vec![size as u8],
+        0xfd..=0xffff => [&[0xfd][..], &(size as u16).to_le_bytes()].concat(),
+        0x10000..=0xffff_ffff => [&[0xfe][..], &(size as u32).to_le_bytes()].concat(),
+        _ => [&[0xff][..], &(size as u64).to_le_bytes()].concat(),
+    }
+}
+
+// `depth` 1 is the top of the stack
+fn stack_top(stack: &[Vec<u8>], depth: usize) -> Result<&Vec<u8>, ScriptError> {
+    stack
+        .len()
+        .checked_sub(depth)
+        .map(|i| &stack[i])
+        .ok_or(ScriptError::InvalidStackOperation)
+}
+
+fn pop(stack: &mut Stack) -> Result<Vec<u8>, ScriptError> {
+    stack.pop().ok_or(ScriptError::InvalidStackOperation)
+}
+
+fn push_bool(stack: &mut Stack, value: bool) {
+    stack.push(if value { vec![1] } else { vec![] });
+}
+
+fn is_true(stack: &[Vec<u8>]) -> bool {
+    stack.last().is_some_and(|top| cast_to_bool(top))
+}
+
+// Bytes of the smallest push of `data`, like `CScript() << data` in Core.
+pub(crate) fn serialize_push(data: &[u8]) -> Vec<u8> {
+    let push = PushBytes::new(data.to_vec()).map_or(Script::OP_FALSE, Script::Push);
+    [push].from_script()
+}
+
+// Whether `push` used the smallest possible push, see CheckMinimalPush in
+// Core.
+fn is_minimal_push(push: &PushBytes) -> bool {
+    match push.data() {
+        // should have been OP_1..OP_16 or OP_1NEGATE
+        [0x01..=0x10] | [0x81] => false,
+        _ => push.is_minimal_encoding(),
+    }
+}
+
+fn is_push_only(script: &[Script]) -> bool {
+    script
+        .iter()
+        .all(|op| op.to_opcode() <= Script::OP_16.to_opcode())
+}
+
+// Removes every push of `data` from `script`, see FindAndDelete in Core. Also
+// returns how many pushes were removed.
+fn find_and_delete(script: &[Script], data: &[u8]) -> (Vec<Script>, usize) {
+    let pattern = serialize_push(data);
+
+    let mut result = Vec::new();
+    let mut found = 0;
+    for op in script {
+        if slice::from_ref(op).from_script() == pattern {
+            found += 1;
+        } else {
+            result.push(op.clone());
+        }
+    }
+
+    (result, found)
+}
+
+// Parses as much of `bytes` as possible. The flag tells whether the end of the
+// script could not be parsed, which is only an error once evaluation gets to it.
+fn parse_prefix(bytes: &[u8]) -> (Vec<Script>, bool) {
+    let mut script = Vec::new();
+    for instruction in Instructions::new(bytes) {
+        match instruction {
+            Ok((_, instruction)) => script.push(instruction.to_op()),
+            Err(_) => return (script, true),
+        }
+    }
+
+    (script, false)
+}
+
+fn is_disabled(op: &Script) -> bool {
+    matches!(
+        op,
+        Script::OP_CAT
+            | Script::OP_SUBSTR
+            | Script::OP_LEFT
+            | Script::OP_RIGHT
+            | Script::OP_INVERT
+            | Script::OP_AND
+            | Script::OP_OR
+            | Script::OP_XOR
+            | Script::OP_2MUL
+            | Script::OP_2DIV
+            | Script::OP_MUL
+            | Script::OP_DIV
+            | Script::OP_MOD
+            | Script::OP_LSHIFT
+            | Script::OP_RSHIFT
+    )
+}
+
+fn is_conditional(op: &Script) -> bool {
+    matches!(
+        op,
+        Script::OP_IF
+            | Script::OP_NOTIF
+            | Script::OP_VERIF
+            | Script::OP_VERNOTIF
+            | Script::OP_ELSE
+            | Script::OP_ENDIF
+    )
+}
+
+// Opcodes that make a tapscript succeed as soon as they appear in it (BIP342).
+fn is_op_success(op: &Script) -> bool {
+    // pushes have opcodes up to 0x4e, none of them is in there
+    matches!(
+        op.to_opcode(),
+        0x50 | 0x62 | 0x7e..=0x81 | 0x83..=0x86 | 0x89..=0x8a | 0x8d..=0x8e | 0x95..=0x99 | 0xbb..=0xfe
+    )
+}
+
+// See IsValidSignatureEncoding in Core (BIP66). `signature` includes the
+// sighash type.
+fn is_valid_signature_encoding(signature: &[u8]) -> bool {
+    // 0x30 [total-length] 0x02 [R-length] [R] 0x02 [S-length] [S] [sighash]
+    let size = signature.len();
+    if !(9..=73).contains(&size) || signature[0] != 0x30 || signature[1] as usize != size - 3 {
+        return false;
+    }
+
+    let r_length = signature[3] as usize;
+    if 5 + r_length >= size {
+        return false;
+    }
+    let s_length = signature[5 + r_length] as usize;
+    if r_length + s_length + 7 != size {
+        return false;
+    }
+
+    let is_valid_integer = |marker: u8, length: usize, integer: &[u8]| {
+        marker == 0x02
+            && length != 0
+            // negative
+            && integer[0] & 0x80 == 0
+            // needless zero padding
+            && !(length > 1 && integer[0] == 0x00 && integer[1] & 0x80 == 0)
+    };
+
+    is_valid_integer(signature[2], r_length, &signature[4..])
+        && is_valid_integer(
+            signature[r_length + 4],
+            s_length,
+            &signature[(r_length + 6)..],
+        )
+}
+
+// Whether S is at most half the curve order. Expects a valid encoding.
+fn is_low_s(signature: &[u8]) -> bool {
+    const HALF_ORDER: [u8; 32] = [
+        0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b,
+        0x20, 0xa0,
+    ];
+
+    let r_length = signature[3] as usize;
+    let s_length = signature[5 + r_length] as usize;
+    let s = &signature[(6 + r_length)..(6 + r_length + s_length)];
+
+    let start = s.iter().position(|byte| *byte != 0).unwrap_or(s.len());
+    let s = &s[start..];
+
+    match s.len() {
+        0..=31 => true,
+        32 => s <= &HALF_ORDER[..],
+        _ => false,
+    }
+}
+
+pub(crate) fn check_signature_encoding(
+    signature: &[u8],
+    flags: VerifyFlags,
+) -> Result<(), ScriptError> {
+    // an empty signature is a compact way to fail a check
+    if signature.is_empty() {
+        return Ok(());
+    }
+
+    let strict_der = VerifyFlags::DERSIG | VerifyFlags::LOW_S | VerifyFlags::STRICTENC;
+    if flags.intersects(strict_der) && !is_valid_signature_encoding(signature) {
+        return Err(ScriptError::SigDer);
+    }
+    if flags.contains(VerifyFlags::LOW_S) && !is_low_s(signature) {
+        return Err(ScriptError::SigHighS);
+    }
+    if flags.contains(VerifyFlags::STRICTENC) {
+        // SIGHASH_ALL, SIGHASH_NONE or SIGHASH_SINGLE, maybe with
+        // SIGHASH_ANYONECANPAY
+        let hash_type = signature[signature.len() - 1] & !0x80;
+        if !(0x01..=0x03).contains(&hash_type) {
+            return Err(ScriptError::SigHashtype);
+        }
+    }
+
+    Ok(())
+}
+
+fn check_pubkey_encoding(
+    pubkey: &[u8],
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+) -> Result<(), ScriptError> {
+    let is_compressed = pubkey.len() == 33 && (pubkey[0] == 0x02 || pubkey[0] == 0x03);
+    let is_uncompressed = pubkey.len() == 65 && pubkey[0] == 0x04;
+
+    if flags.contains(VerifyFlags::STRICTENC) && !is_compressed && !is_uncompressed {
+        return Err(ScriptError::PubkeyType);
+    }
+    if flags.contains(VerifyFlags::WITNESS_PUBKEYTYPE)
+        && sig_version == SigVersion::WitnessV0
+        && !is_compressed
+    {
+        return Err(ScriptError::WitnessPubkeyType);
+    }
+
+    Ok(())
+}
+
+fn check_schnorr_signature(
+    signature: &[u8],
+    pubkey: &[u8],
+    sig_version: SigVersion,
+    exec_data: &ExecData,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    match signature.len() {
+        64 => {}
+        65 => {
+            // SIGHASH_DEFAULT must be implicit
+            let hash_type = signature[64];
+            if !matches!(hash_type, 0x01..=0x03 | 0x81..=0x83) {
+                return Err(ScriptError::SchnorrSigHashtype);
+            }
+        }
+        _ => return Err(ScriptError::SchnorrSigSize),
+    }
+
+    if !checker.check_schnorr_sig(signature, pubkey, sig_version, exec_data) {
+        return Err(ScriptError::SchnorrSig);
+    }
+
+    Ok(())
+}
+
+// OP_CHECKSIG and friends in tapscript, see EvalChecksigTapscript in Core.
+fn check_tapscript_sig(
+    signature: &[u8],
+    pubkey: &[u8],
+    flags: VerifyFlags,
+    exec_data: &mut ExecData,
+    checker: &impl SignatureChecker,
+) -> Result<bool, ScriptError> {
+    let success = !signature.is_empty();
+    if success {
+        exec_data.validation_weight_left -= VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+        if exec_data.validation_weight_left < 0 {
+            return Err(ScriptError::TapscriptValidationWeight);
+        }
+    }
+
+    match pubkey.len() {
+        0 => return Err(ScriptError::TapscriptEmptyPubkey),
+        32 => {
+            if success {
+                check_schnorr_signature(
+                    signature,
+                    pubkey,
+                    SigVersion::Tapscript,
+                    exec_data,
+                    checker,
+                )?;
+            }
+        }
+        // unknown key types are valid, so they can be given a meaning later
+        _ => {
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE) {
+                return Err(ScriptError::DiscourageUpgradablePubkeyType);
+            }
+        }
+    }
+
+    Ok(success)
+}
+
+// Runs `script` on top of `stack`, which keeps whatever the script left on it.
+// Taproot key path spends have no script, so `sig_version` can't be
+// `SigVersion::Taproot`.
+pub fn eval_script(
+    stack: &mut Stack,
+    script: &[Script],
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    // a script run on its own has no witness to limit its signatures
+    let mut exec_data = ExecData {
+        validation_weight_left: i64::MAX,
+        ..ExecData::default()
+    };
+
+    eval(
+        stack,
+        script,
+        false,
+        flags,
+        sig_version,
+        &mut exec_data,
+        checker,
+    )
+}
+
+// `truncated` means `script` is the part of a script that could be parsed, so
+// reaching its end is an error.
+fn eval(
+    stack: &mut Stack,
+    script: &[Script],
+    truncated: bool,
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+    exec_data: &mut ExecData,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    let is_legacy = matches!(sig_version, SigVersion::Base | SigVersion::WitnessV0);
+    if is_legacy && script.from_script().len() > MAX_SCRIPT_SIZE {
+        return Err(ScriptError::ScriptSize);
+    }
+
+    let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+    let num =
+        |bytes: &[u8]| ScriptNum::from_bytes(bytes, require_minimal, ScriptNum::DEFAULT_MAX_SIZE);
+
+    let mut alt_stack: Stack = Vec::new();
+    // one entry per open OP_IF, telling whether its branch is being executed
+    let mut exec_stack: Vec<bool> = Vec::new();
+    let mut op_count = 0;
+    let mut code_separator = 0;
+
+    for (i, op) in script.iter().enumerate() {
+        let executing = !exec_stack.contains(&false);
+        let position = i as u32;
+
+        if let Script::Push(push) = op {
+            if push.data().len() > MAX_SCRIPT_ELEMENT_SIZE {
+                return Err(ScriptError::PushSize);
+            }
+            if executing {
+                if require_minimal && !is_minimal_push(push) {
+                    return Err(ScriptError::MinimalData);
+                }
+                stack.push(push.data().to_vec());
+            }
+        } else {
+            if is_legacy && op.to_opcode() > Script::OP_16.to_opcode() {
+                op_count += 1;
+                if op_count > MAX_OPS_PER_SCRIPT {
+                    return Err(ScriptError::OpCount);
+                }
+            }
+
+            if is_disabled(op) {
+                return Err(ScriptError::DisabledOpcode);
+            }
+
+            if *op == Script::OP_CODESEPARATOR
+                && sig_version == SigVersion::Base
+                && flags.contains(VerifyFlags::CONST_SCRIPTCODE)
+            {
+                return Err(ScriptError::OpCodeSeparator);
+            }
+
+            if executing || is_conditional(op) {
+                match op {
+                    Script::OP_FALSE => stack.push(vec![]),
+                    Script::OP_1NEGATE
+                    | Script::OP_TRUE
+                    | Script::OP_2
+                    | Script::OP_3
+                    | Script::OP_4
+                    | Script::OP_5
+                    | Script::OP_6
+                    | Script::OP_7
+                    | Script::OP_8
+                    | Script::OP_9
+                    | Script::OP_10
+                    | Script::OP_11
+                    | Script::OP_12
+                    | Script::OP_13
+                    | Script::OP_14
+                    | Script::OP_15
+                    | Script::OP_16 => {
+                        let n = op.to_opcode() as i64 - (Script::OP_TRUE.to_opcode() as i64 - 1);
+                        stack.push(ScriptNum(n).to_bytes());
+                    }
+
+                    Script::OP_NOP => {}
+                    Script::OP_CHECKLOCKTIMEVERIFY
+                        if flags.contains(VerifyFlags::CHECKLOCKTIMEVERIFY) =>
+                    {
+                        // lock times go up to 2^32 - 1, so 5 bytes are allowed
+                        let lock_time =
+                            ScriptNum::from_bytes(stack_top(stack, 1)?, require_minimal, 5)?.0;
+                        if lock_time < 0 {
+                            return Err(ScriptError::NegativeLocktime);
+                        }
+                        if !checker.check_lock_time(lock_time) {
+                            return Err(ScriptError::UnsatisfiedLocktime);
+                        }
+                    }
+                    Script::OP_CHECKSEQUENCEVERIFY
+                        if flags.contains(VerifyFlags::CHECKSEQUENCEVERIFY) =>
+                    {
+                        let sequence =
+                            ScriptNum::from_bytes(stack_top(stack, 1)?, require_minimal, 5)?.0;
+                        if sequence < 0 {
+                            return Err(ScriptError::NegativeLocktime);
+                        }
+                        // without the disable flag, this behaves as a NOP
+                        if sequence & SEQUENCE_LOCKTIME_DISABLE_FLAG == 0
+                            && !checker.check_sequence(sequence)
+                        {
+                            return Err(ScriptError::UnsatisfiedLocktime);
+                        }
+                    }
+                    Script::OP_NOP1
+                    | Script::OP_CHECKLOCKTIMEVERIFY
+                    | Script::OP_CHECKSEQUENCEVERIFY
+                    | Script::OP_NOP4
+                    | Script::OP_NOP5
+                    | Script::OP_NOP6
+                    | Script::OP_NOP7
+                    | Script::OP_NOP8
+                    | Script::OP_NOP9
+                    | Script::OP_NOP10 => {
+                        if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS) {
+                            return Err(ScriptError::DiscourageUpgradableNops);
+                        }
+                    }
+
+                    Script::OP_IF | Script::OP_NOTIF => {
+                        let mut value = false;
+                        if executing {
+                            let top = stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+
+                            let is_minimal = top.is_empty() || top == [1];
+                            if sig_version == SigVersion::Tapscript && !is_minimal {
+                                return Err(ScriptError::TapscriptMinimalIf);
+                            }
+                            if sig_version == SigVersion::WitnessV0
+                                && flags.contains(VerifyFlags::MINIMALIF)
+                                && !is_minimal
+                            {
+                                return Err(ScriptError::MinimalIf);
+                            }
+
+                            value = cast_to_bool(&top);
+                            if *op == Script::OP_NOTIF {
+                                value = !value;
+                            }
+                        }
+                        exec_stack.push(value);
+                    }
+                    Script::OP_ELSE => {
+                        let branch = exec_stack
+                            .last_mut()
+                            .ok_or(ScriptError::UnbalancedConditional)?;
+                        *branch = !*branch;
+                    }
+                    Script::OP_ENDIF => {
+                        exec_stack.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                    }
+                    Script::OP_VERIFY => {
+                        if !cast_to_bool(stack_top(stack, 1)?) {
+                            return Err(ScriptError::Verify);
+                        }
+                        stack.pop();
+                    }
+                    Script::OP_RETURN => return Err(ScriptError::OpReturn),
+
+                    Script::OP_TOALTSTACK => alt_stack.push(pop(stack)?),
+                    Script::OP_FROMALTSTACK => {
+                        let value = alt_stack
+                            .pop()
+                            .ok_or(ScriptError::InvalidAltstackOperation)?;
+                        stack.push(value);
+                    }
+                    Script::OP_2DROP => {
+                        stack_top(stack, 2)?;
+                        stack.truncate(stack.len() - 2);
+                    }
+                    Script::OP_2DUP => {
+                        let values = [stack_top(stack, 2)?.clone(), stack_top(stack, 1)?.clone()];
+                        stack.extend(values);
+                    }
+                    Script::OP_3DUP => {
+                        let values = [
+                            stack_top(stack, 3)?.clone(),
+                            stack_top(stack, 2)?.clone(),
+                            stack_top(stack, 1)?.clone(),
+                        ];
+                        stack.extend(values);
+                    }
+                    Script::OP_2OVER => {
+                        let values = [stack_top(stack, 4)?.clone(), stack_top(stack, 3)?.clone()];
+                        stack.extend(values);
+                    }
+                    Script::OP_2ROT => {
+                        stack_top(stack, 6)?;
+                        let start = stack.len() - 6;
+                        let values: Vec<Vec<u8>> = stack.drain(start..(start + 2)).collect();
+                        stack.extend(values);
+                    }
+                    Script::OP_2SWAP => {
+                        stack_top(stack, 4)?;
+                        let start = stack.len() - 4;
+                        stack[start..].rotate_left(2);
+                    }
+                    Script::OP_IFDUP => {
+                        let value = stack_top(stack, 1)?;
+                        if cast_to_bool(value) {
+                            stack.push(value.clone());
+                        }
+                    }
+                    Script::OP_DEPTH => stack.push(ScriptNum(stack.len() as i64).to_bytes()),
+                    Script::OP_DROP => {
+                        pop(stack)?;
+                    }
+                    Script::OP_DUP => stack.push(stack_top(stack, 1)?.clone()),
+                    Script::OP_NIP => {
+                        stack_top(stack, 2)?;
+                        stack.remove(stack.len() - 2);
+                    }
+                    Script::OP_OVER => stack.push(stack_top(stack, 2)?.clone()),
+                    Script::OP_PICK | Script::OP_ROLL => {
+                        let n = num(stack_top(stack, 1)?)?.to_i32();
+                        stack.pop();
+                        if n < 0 || n as usize >= stack.len() {
+                            return Err(ScriptError::InvalidStackOperation);
+                        }
+
+                        let index = stack.len() - 1 - n as usize;
+                        let value = if *op == Script::OP_ROLL {
+                            stack.remove(index)
+                        } else {
+                            stack[index].clone()
+                        };
+                        stack.push(value);
+                    }
+                    Script::OP_ROT => {
+                        stack_top(stack, 3)?;
+                        let start = stack.len() - 3;
+                        stack[start..].rotate_left(1);
+                    }
+                    Script::OP_SWAP => {
+                        stack_top(stack, 2)?;
+                        let start = stack.len() - 2;
+                        stack.swap(start, start + 1);
+                    }
+                    Script::OP_TUCK => {
+                        let value = stack_top(stack, 1)?.clone();
+                        stack_top(stack, 2)?;
+                        stack.insert(stack.len() - 2, value);
+                    }
+
+                    Script::OP_SIZE => {
+                        let size = stack_top(stack, 1)?.len();
+                        stack.push(ScriptNum(size as i64).to_bytes());
+                    }
+
+                    Script::OP_EQUAL | Script::OP_EQUALVERIFY => {
+                        let equal = stack_top(stack, 2)? == stack_top(stack, 1)?;
+                        stack.truncate(stack.len() - 2);
+                        push_bool(stack, equal);
+
+                        if *op == Script::OP_EQUALVERIFY {
+                            if !equal {
+                                return Err(ScriptError::EqualVerify);
+                            }
+                            stack.pop();
+                        }
+                    }
+
+                    Script::OP_1ADD
+                    | Script::OP_1SUB
+                    | Script::OP_NEGATE
+                    | Script::OP_ABS
+                    | Script::OP_NOT
+                    | Script::OP_0NOTEQUAL => {
+                        let ScriptNum(n) = num(stack_top(stack, 1)?)?;
+                        stack.pop();
+
+                        let result = match op {
+                            Script::OP_1ADD => n + 1,
+                            Script::OP_1SUB => n - 1,
+                            Script::OP_NEGATE => -n,
+                            Script::OP_ABS => n.abs(),
+                            Script::OP_NOT => (n == 0) as i64,
+                            _ => (n != 0) as i64,
+                        };
+                        stack.push(ScriptNum(result).to_bytes());
+                    }
+
+                    Script::OP_ADD
+                    | Script::OP_SUB
+                    | Script::OP_BOOLAND
+                    | Script::OP_BOOLOR
+                    | Script::OP_NUMEQUAL
+                    | Script::OP_NUMEQUALVERIFY
+                    | Script::OP_NUMNOTEQUAL
+                    | Script::OP_LESSTHAN
+                    | Script::OP_GREATERTHAN
+                    | Script::OP_LESSTHANOREQUAL
+                    | Script::OP_GREATERTHANOREQUAL
+                    | Script::OP_MIN
+                    | Script::OP_MAX => {
+                        let ScriptNum(a) = num(stack_top(stack, 2)?)?;
+                        let ScriptNum(b) = num(stack_top(stack, 1)?)?;
+                        stack.truncate(stack.len() - 2);
+
+                        let result = match op {
+                            Script::OP_ADD => a + b,
+                            Script::OP_SUB => a - b,
+                            Script::OP_BOOLAND => (a != 0 && b != 0) as i64,
+                            Script::OP_BOOLOR => (a != 0 || b != 0) as i64,
+                            Script::OP_NUMEQUAL | Script::OP_NUMEQUALVERIFY => (a == b) as i64,
+                            Script::OP_NUMNOTEQUAL => (a != b) as i64,
+                            Script::OP_LESSTHAN => (a < b) as i64,
+                            Script::OP_GREATERTHAN => (a > b) as i64,
+                            Script::OP_LESSTHANOREQUAL => (a <= b) as i64,
+                            Script::OP_GREATERTHANOREQUAL => (a >= b) as i64,
+                            Script::OP_MIN => a.min(b),
+                            _ => a.max(b),
+                        };
+                        stack.push(ScriptNum(result).to_bytes());
+
+                        if *op == Script::OP_NUMEQUALVERIFY {
+                            if result == 0 {
+                                return Err(ScriptError::NumEqualVerify);
+                            }
+                            stack.pop();
+                        }
+                    }
+                    Script::OP_WITHIN => {
+                        let ScriptNum(x) = num(stack_top(stack, 3)?)?;
+                        let ScriptNum(min) = num(stack_top(stack, 2)?)?;
+                        let ScriptNum(max) = num(stack_top(stack, 1)?)?;
+                        stack.truncate(stack.len() - 3);
+                        push_bool(stack, min <= x && x < max);
+                    }
+
+                    Script::OP_RIPEMD160
+                    | Script::OP_SHA1
+                    | Script::OP_SHA256
+                    | Script::OP_HASH160
+                    | Script::OP_HASH256 => {
+                        let value = pop(stack)?;
+                        let result = match op {
+                            Script::OP_RIPEMD160 => hash(Ripemd160::new(), &value),
+                            Script::OP_SHA1 => hash(Sha1::new(), &value),
+                            Script::OP_SHA256 => hash(Sha256::new(), &value),
+                            Script::OP_HASH160 => {
+                                hash(Ripemd160::new(), &hash(Sha256::new(), &value))
+                            }
+                            _ => hash(Sha256::new(), &hash(Sha256::new(), &value)),
+                        };
+                        stack.push(result);
+                    }
+                    Script::OP_CODESEPARATOR => {
+                        code_separator = i + 1;
+                        exec_data.codeseparator_pos = position;
+                    }
+                    Script::OP_CHECKSIG | Script::OP_CHECKSIGVERIFY => {
+                        let signature = stack_top(stack, 2)?;
+                        let pubkey = stack_top(stack, 1)?;
+
+                        let success = if sig_version == SigVersion::Tapscript {
+                            check_tapscript_sig(signature, pubkey, flags, exec_data, checker)?
+                        } else {
+                            check_ecdsa_sig(
+                                signature,
+                                pubkey,
+                                &script[code_separator..],
+                                flags,
+                                sig_version,
+                                checker,
+                            )?
+                        };
+                        stack.truncate(stack.len() - 2);
+                        push_bool(stack, success);
+
+                        if *op == Script::OP_CHECKSIGVERIFY {
+                            if !success {
+                                return Err(ScriptError::CheckSigVerify);
+                            }
+                            stack.pop();
+                        }
+                    }
+                    Script::OP_CHECKSIGADD if sig_version == SigVersion::Tapscript => {
+                        let signature = stack_top(stack, 3)?;
+                        let ScriptNum(n) = num(stack_top(stack, 2)?)?;
+                        let pubkey = stack_top(stack, 1)?;
+
+                        let success =
+                            check_tapscript_sig(signature, pubkey, flags, exec_data, checker)?;
+                        stack.truncate(stack.len() - 3);
+                        stack.push(ScriptNum(n + success as i64).to_bytes());
+                    }
+                    Script::OP_CHECKMULTISIG | Script::OP_CHECKMULTISIGVERIFY => {
+                        if sig_version == SigVersion::Tapscript {
+                            return Err(ScriptError::TapscriptCheckMultisig);
+                        }
+
+                        let success = check_multisig(
+                            stack,
+                            &script[code_separator..],
+                            &mut op_count,
+                            flags,
+                            sig_version,
+                            checker,
+                        )?;
+
+                        if *op == Script::OP_CHECKMULTISIGVERIFY {
+                            if !success {
+                                return Err(ScriptError::CheckMultisigVerify);
+                            }
+                            stack.pop();
+                        }
+                    }
+
+                    // OP_CHECKSIGADD outside of tapscript is also a bad opcode
+                    _ => return Err(ScriptError::BadOpcode),
+                }
+            }
+        }
+
+        if stack.len() + alt_stack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+
+    if truncated {
+        return Err(ScriptError::BadOpcode);
+    }
+
+    if !exec_stack.is_empty() {
+        return Err(ScriptError::UnbalancedConditional);
+    }
+
+    Ok(())
+}
+
+fn check_ecdsa_sig(
+    signature: &[u8],
+    pubkey: &[u8],
+    script_code: &[Script],
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+    checker: &impl SignatureChecker,
+) -> Result<bool, ScriptError> {
+    let mut script_code = script_code.to_vec();
+    // signatures can't sign themselves, so they are removed from what they
+    // commit to (only before segwit)
+    if sig_version == SigVersion::Base {
+        let (without_signature, found) = find_and_delete(&script_code, signature);
+        if found > 0 && flags.contains(VerifyFlags::CONST_SCRIPTCODE) {
+            return Err(ScriptError::SigFindAndDelete);
+        }
+        script_code = without_signature;
+    }
+
+    check_signature_encoding(signature, flags)?;
+    check_pubkey_encoding(pubkey, flags, sig_version)?;
+
+    let success =
+        !signature.is_empty() && checker.check_sig(signature, pubkey, &script_code, sig_version);
+    if !success && flags.contains(VerifyFlags::NULLFAIL) && !signature.is_empty() {
+        return Err(ScriptError::SigNullFail);
+    }
+
+    Ok(success)
+}
+
+// The stack has, from the top: the number of keys, the keys, the number of
+// signatures, the signatures and an extra element (consumed because of an
+// off-by-one bug in the original client). Pushes whether the signatures match
+// the keys, in order, and returns it.
+fn check_multisig(
+    stack: &mut Stack,
+    script_code: &[Script],
+    op_count: &mut usize,
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+    checker: &impl SignatureChecker,
+) -> Result<bool, ScriptError> {
+    let require_minimal = flags.contains(VerifyFlags::MINIMALDATA);
+    let num =
+        |bytes: &[u8]| ScriptNum::from_bytes(bytes, require_minimal, ScriptNum::DEFAULT_MAX_SIZE);
+
+    let keys_count = num(stack_top(stack, 1)?)?.to_i32();
+    if !(0..=MAX_PUBKEYS_PER_MULTISIG).contains(&keys_count) {
+        return Err(ScriptError::PubkeyCount);
+    }
+    let keys_count = keys_count as usize;
+
+    *op_count += keys_count;
+    if *op_count > MAX_OPS_PER_SCRIPT {
+        return Err(ScriptError::OpCount);
+    }
+
+    let sigs_depth = 2 + keys_count;
+    let sigs_count = num(stack_top(stack, sigs_depth)?)?.to_i32();
+    if sigs_count < 0 || sigs_count as usize > keys_count {
+        return Err(ScriptError::SigCount);
+    }
+    let sigs_count = sigs_count as usize;
+
+    // every element but the dummy one, which must be there too
+    let total = sigs_depth + sigs_count;
+    stack_top(stack, total + 1)?;
+
+    let elements = stack.split_off(stack.len() - total);
+    // topmost first, that's the order Core checks them in
+    let keys: Vec<&Vec<u8>> = elements[(total - 1 - keys_count)..(total - 1)]
+        .iter()
+        .rev()
+        .collect();
+    let signatures: Vec<&Vec<u8>> = elements[..sigs_count].iter().rev().collect();
+
+    let mut script_code = script_code.to_vec();
+    if sig_version == SigVersion::Base {
+        for signature in signatures.iter() {
+            let (without_signature, found) = find_and_delete(&script_code, signature);
+            if found > 0 && flags.contains(VerifyFlags::CONST_SCRIPTCODE) {
+                return Err(ScriptError::SigFindAndDelete);
+            }
+            script_code = without_signature;
+        }
+    }
+
+    let mut success = true;
+    let (mut key, mut signature) = (0, 0);
+    while success && signature < sigs_count {
+        // the order in which keys and signatures are checked is only visible
+        // with STRICTENC
+        check_signature_encoding(signatures[signature], flags)?;
+        check_pubkey_encoding(keys[key], flags, sig_version)?;
+
+        if !signatures[signature].is_empty()
+            && checker.check_sig(signatures[signature], keys[key], &script_code, sig_version)
+        {
+            signature += 1;
+        }
+        key += 1;
+
+        // there are not enough keys left for the remaining signatures
+        if sigs_count - signature > keys_count - key {
+            success = false;
+        }
+    }
+
+    if !success
+        && flags.contains(VerifyFlags::NULLFAIL)
+        && signatures.iter().any(|signature| !signature.is_empty())
+    {
+        return Err(ScriptError::SigNullFail);
+    }
+
+    let dummy = pop(stack)?;
+    if flags.contains(VerifyFlags::NULLDUMMY) && !dummy.is_empty() {
+        return Err(ScriptError::SigNullDummy);
+    }
+
+    push_bool(stack, success);
+
+    Ok(success)
+}
+
+// The version and program of a witness output: a version opcode followed by a
+// single push of 2 to 40 bytes.
+fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    if script.len() < 4 || script.len() > 42 || script[1] as usize + 2 != script.len() {
+        return None;
+    }
+
+    match script[0] {
+        0x00 => Some((0, &script[2..])),
+        0x51..=0x60 => Some((script[0] - 0x50, &script[2..])),
+        _ => None,
+    }
+}
+
+fn is_p2sh(script: &[u8]) -> bool {
+    script.len() == 23
+        && script[0] == Script::OP_HASH160.to_opcode()
+        && script[1] == 0x14
+        && script[22] == Script::OP_EQUAL.to_opcode()
+}
+
+// Runs a P2WSH witness script or a tapscript on the rest of the witness, which
+// must end with a single true element.
+fn execute_witness_script(
+    mut stack: Stack,
+    script: &[Script],
+    truncated: bool,
+    flags: VerifyFlags,
+    sig_version: SigVersion,
+    exec_data: &mut ExecData,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    if sig_version == SigVersion::Tapscript {
+        if script.iter().any(is_op_success) {
+            if flags.contains(VerifyFlags::DISCOURAGE_OP_SUCCESS) {
+                return Err(ScriptError::DiscourageOpSuccess);
+            }
+            return Ok(());
+        }
+        if truncated {
+            return Err(ScriptError::BadOpcode);
+        }
+        if stack.len() > MAX_STACK_SIZE {
+            return Err(ScriptError::StackSize);
+        }
+    }
+
+    if stack
+        .iter()
+        .any(|element| element.len() > MAX_SCRIPT_ELEMENT_SIZE)
+    {
+        return Err(ScriptError::PushSize);
+    }
+
+    eval(
+        &mut stack,
+        script,
+        truncated,
+        flags,
+        sig_version,
+        exec_data,
+        checker,
+    )?;
+
+    if stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+    if !cast_to_bool(&stack[0]) {
+        return Err(ScriptError::EvalFalse);
+    }
+
+    Ok(())
+}
+
+fn verify_witness_program(
+    witness: &[Vec<u8>],
+    version: u8,
+    program: &[u8],
+    flags: VerifyFlags,
+    is_p2sh: bool,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    let mut stack = witness.to_vec();
+    let mut exec_data = ExecData::default();
+
+    match (version, program.len()) {
+        // P2WSH: the last element is the script, which hashes to the program
+        (0, 32) => {
+            let script_bytes = stack.pop().ok_or(ScriptError::WitnessProgramWitnessEmpty)?;
+            if hash(Sha256::new(), &script_bytes) != program {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+
+            let (script, truncated) = parse_prefix(&script_bytes);
+            execute_witness_script(
+                stack,
+                &script,
+                truncated,
+                flags,
+                SigVersion::WitnessV0,
+                &mut exec_data,
+                checker,
+            )
+        }
+        // P2WPKH: a signature and a key, checked like P2PKH
+        (0, 20) => {
+            if stack.len() != 2 {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+
+            let script = vec![
+                Script::OP_DUP,
+                Script::OP_HASH160,
+                Script::push(program),
+                Script::OP_EQUALVERIFY,
+                Script::OP_CHECKSIG,
+            ];
+            execute_witness_script(
+                stack,
+                &script,
+                false,
+                flags,
+                SigVersion::WitnessV0,
+                &mut exec_data,
+                checker,
+            )
+        }
+        (0, _) => Err(ScriptError::WitnessProgramWrongLength),
+        (1, 32) if !is_p2sh => {
+            if !flags.contains(VerifyFlags::TAPROOT) {
+                return Ok(());
+            }
+
+            if stack.is_empty() {
+                return Err(ScriptError::WitnessProgramWitnessEmpty);
+            }
+            if stack.len() >= 2 && stack[stack.len() - 1].first() == Some(&ANNEX_TAG) {
+                let annex = stack.pop().expect("the witness has at least 2 elements");
+                exec_data.annex = Some(annex[1..].to_vec());
+            }
+
+            // key path: a signature for the program itself
+            if stack.len() == 1 {
+                return check_schnorr_signature(
+                    &stack[0],
+                    program,
+                    SigVersion::Taproot,
+                    &exec_data,
+                    checker,
+                );
+            }
+
+            // script path: the script and a proof that the program commits to it
+            let control = stack.pop().expect("the witness has at least 2 elements");
+            let script_bytes = stack.pop().expect("the witness has at least 2 elements");
+            if control.len() < TAPROOT_CONTROL_BASE_SIZE
+                || control.len()
+                    > TAPROOT_CONTROL_BASE_SIZE
+                        + TAPROOT_CONTROL_NODE_SIZE * TAPROOT_CONTROL_MAX_NODE_COUNT
+                || !(control.len() - TAPROOT_CONTROL_BASE_SIZE)
+                    .is_multiple_of(TAPROOT_CONTROL_NODE_SIZE)
+            {
+                return Err(ScriptError::TaprootWrongControlSize);
+            }
+
+            let leaf_version = control[0] & 0xfe;
+            let tapleaf_hash = tagged_hash(
+                "TapLeaf",
+                &[
+                    &[leaf_version][..],
+                    &compact_size(script_bytes.len()),
+                    &script_bytes,
+                ]
+                .concat(),
+            );
+            exec_data.tapleaf_hash = Some(tapleaf_hash);
+
+            let mut merkle_root = tapleaf_hash;
+            for node in control[TAPROOT_CONTROL_BASE_SIZE..].chunks(TAPROOT_CONTROL_NODE_SIZE) {
+                let branch = if merkle_root[..] < *node {
+                    [&merkle_root[..], node].concat()
+                } else {
+                    [node, &merkle_root[..]].concat()
+                };
+                merkle_root = tagged_hash("TapBranch", &branch);
+            }
+
+            let internal_key = &control[1..TAPROOT_CONTROL_BASE_SIZE];
+            if !checker.check_tap_tweak(internal_key, program, &merkle_root, control[0] & 1 == 1) {
+                return Err(ScriptError::WitnessProgramMismatch);
+            }
+
+            if leaf_version != TAPROOT_LEAF_TAPSCRIPT {
+                if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION) {
+                    return Err(ScriptError::DiscourageUpgradableTaprootVersion);
+                }
+                return Ok(());
+            }
+
+            let witness_size = compact_size(witness.len()).len()
+                + witness
+                    .iter()
+                    .map(|element| compact_size(element.len()).len() + element.len())
+                    .sum::<usize>();
+            exec_data.validation_weight_left =
+                witness_size as i64 + VALIDATION_WEIGHT_PER_SIGOP_PASSED;
+
+            let (script, truncated) = parse_prefix(&script_bytes);
+            execute_witness_script(
+                stack,
+                &script,
+                truncated,
+                flags,
+                SigVersion::Tapscript,
+                &mut exec_data,
+                checker,
+            )
+        }
+        // unknown versions are valid, so they can be given a meaning later
+        _ => {
+            if flags.contains(VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM) {
+                return Err(ScriptError::DiscourageUpgradableWitnessProgram);
+            }
+            Ok(())
+        }
+    }
+}
+
+// Runs the scriptSig and then the scriptPubKey with what it left on the stack,
+// then the redeem script or the witness if the scriptPubKey asks for them. The
+// spend is valid if the top of the stack ends up true.
+pub fn verify_script(
+    script_sig: &[Script],
+    script_pubkey: &[Script],
+    witness: &[Vec<u8>],
+    flags: VerifyFlags,
+    checker: &impl SignatureChecker,
+) -> Result<(), ScriptError> {
+    if flags.contains(VerifyFlags::SIGPUSHONLY) && !is_push_only(script_sig) {
+        return Err(ScriptError::SigPushOnly);
+    }
+
+    let mut stack: Stack = Vec::new();
+    eval_script(&mut stack, script_sig, flags, SigVersion::Base, checker)?;
+    let script_sig_stack = stack.clone();
+
+    let script_pubkey_bytes = script_pubkey.from_script();
+    eval_script(&mut stack, script_pubkey, flags, SigVersion::Base, checker)?;
+    if !is_true(&stack) {
+        return Err(ScriptError::EvalFalse);
+    }
+
+    let mut had_witness = false;
+    if flags.contains(VerifyFlags::WITNESS) {
+        if let Some((version, program)) = witness_program(&script_pubkey_bytes) {
+            had_witness = true;
+            if !script_sig.is_empty() {
+                return Err(ScriptError::WitnessMalleated);
+            }
+
+            verify_witness_program(witness, version, program, flags, false, checker)?;
+            // the witness already had its own clean stack check
+            stack.truncate(1);
+        }
+    }
+
+    if flags.contains(VerifyFlags::P2SH) && is_p2sh(&script_pubkey_bytes) {
+        if !is_push_only(script_sig) {
+            return Err(ScriptError::SigPushOnly);
+        }
+
+        stack = script_sig_stack;
+        let redeem_script_bytes = stack
+            .pop()
+            .expect("the scriptPubKey hashed an element of the stack");
+        let (redeem_script, truncated) = parse_prefix(&redeem_script_bytes);
+
+        eval(
+            &mut stack,
+            &redeem_script,
+            truncated,
+            flags,
+            SigVersion::Base,
+            &mut ExecData::default(),
+            checker,
+        )?;
+        if !is_true(&stack) {
+            return Err(ScriptError::EvalFalse);
+        }
+
+        if flags.contains(VerifyFlags::WITNESS) {
+            if let Some((version, program)) = witness_program(&redeem_script_bytes) {
+                had_witness = true;
+                // the scriptSig must be the push of the redeem script alone
+                if script_sig.from_script() != serialize_push(&redeem_script_bytes) {
+                    return Err(ScriptError::WitnessMalleatedP2sh);
+                }
+
+                verify_witness_program(witness, version, program, flags, true, checker)?;
+                stack.truncate(1);
+            }
+        }
+    }
+
+    if flags.contains(VerifyFlags::CLEANSTACK) && stack.len() != 1 {
+        return Err(ScriptError::CleanStack);
+    }
+
+    if flags.contains(VerifyFlags::WITNESS) && !had_witness && !witness.is_empty() {
+        return Err(ScriptError::WitnessUnexpected);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script::{PushEncoding, ToScript};
+
+    fn run(hex_script: &[u8]) -> Result<Stack, ScriptError> {
+        let mut stack = Vec::new();
+        eval_script(
+            &mut stack,
+            &hex_script.to_script().unwrap(),
+            VerifyFlags::NONE,
+            SigVersion::Base,
+            &BaseSignatureChecker,
+        )?;
+
+        Ok(stack)
+    }
+
+    // Accepts a signature when it is the pubkey followed by a sighash byte.
+    struct FakeChecker;
+
+    impl SignatureChecker for FakeChecker {
+        fn check_sig(
+            &self,
+            signature: &[u8],
+            pubkey: &[u8],
+            _script_code: &[Script],
+            _sig_version: SigVersion,
+        ) -> bool {
+            &signature[..(signature.len() - 1)] == pubkey
+        }
+    }
+
+    #[test]
+    fn script_num() {
+        let cases: [(i64, &[u8]); 9] = [
+            (0, &[]),
+            (1, &[0x01]),
+            (-1, &[0x81]),
+            (127, &[0x7f]),
+            (128, &[0x80, 0x00]),
+            (-128, &[0x80, 0x80]),
+            (255, &[0xff, 0x00]),
+            (-32768, &[0x00, 0x80, 0x80]),
+            (2147483647, &[0xff, 0xff, 0xff, 0x7f]),
+        ];
+
+        for (n, bytes) in cases.iter() {
+            assert_eq!(ScriptNum(*n).to_bytes(), bytes.to_vec());
+            assert_eq!(
+                ScriptNum::from_bytes(bytes, false, 4).unwrap(),
+                ScriptNum(*n)
+            );
+        }
+
+        // not minimally encoded, but still valid
+        assert_eq!(
+            ScriptNum::from_bytes(&[0x01, 0x00], false, 4).unwrap(),
+            ScriptNum(1)
+        );
+        assert_eq!(
+            ScriptNum::from_bytes(&[0x80], false, 4).unwrap(),
+            ScriptNum(0)
+        );
+        assert_eq!(
+            ScriptNum::from_bytes(&[0x00; 5], false, 4),
+            Err(ScriptError::UnknownError)
+        );
+    }
+
+    #[test]
+    fn arithmetic() {
+        // 2 3 ADD 5 NUMEQUAL
+        assert_eq!(run(&[0x52, 0x53, 0x93, 0x55, 0x9c]).unwrap(), vec![vec![1]]);
+        // 1 NEGATE 1ADD
+        assert_eq!(run(&[0x51, 0x8f, 0x8b]).unwrap(), vec![Vec::<u8>::new()]);
+        // 0x7fffffff 1ADD overflows to 5 bytes
+        assert_eq!(
+            run(&[0x04, 0xff, 0xff, 0xff, 0x7f, 0x8b]).unwrap(),
+            vec![vec![0x00, 0x00, 0x00, 0x80, 0x00]]
+        );
+        // but such a result can't be an operand
+        assert_eq!(
+            run(&[0x04, 0xff, 0xff, 0xff, 0x7f, 0x8b, 0x8b]),
+            Err(ScriptError::UnknownError)
+        );
+        // 3 2 5 WITHIN
+        assert_eq!(run(&[0x53, 0x52, 0x55, 0xa5]).unwrap(), vec![vec![1]]);
+        // 7 2 MIN 9 MAX
+        assert_eq!(run(&[0x57, 0x52, 0xa3, 0x59, 0xa4]).unwrap(), vec![vec![9]]);
+    }
+
+    #[test]
+    fn conditionals() {
+        // 1 IF 2 ELSE 3 ENDIF
+        assert_eq!(
+            run(&[0x51, 0x63, 0x52, 0x67, 0x53, 0x68]).unwrap(),
+            vec![vec![2]]
+        );
+        // 0 IF 2 ELSE 3 ENDIF
+        assert_eq!(
+            run(&[0x00, 0x63, 0x52, 0x67, 0x53, 0x68]).unwrap(),
+            vec![vec![3]]
+        );
+        // 0 NOTIF 1 IF 4 ENDIF ENDIF
+        assert_eq!(
+            run(&[0x00, 0x64, 0x51, 0x63, 0x54, 0x68, 0x68]).unwrap(),
+            vec![vec![4]]
+        );
+        // ELSE can be repeated
+        // 1 IF ELSE ELSE 5 ENDIF
+        assert_eq!(
+            run(&[0x51, 0x63, 0x67, 0x67, 0x55, 0x68]).unwrap(),
+            vec![vec![5]]
+        );
+        // unexecuted branches may hold anything but disabled opcodes and
+        // OP_VERIF/OP_VERNOTIF
+        assert_eq!(run(&[0x00, 0x63, 0x6a, 0xff, 0x68]).unwrap(), Stack::new());
+        assert_eq!(
+            run(&[0x00, 0x63, 0x7e, 0x68]),
+            Err(ScriptError::DisabledOpcode)
+        );
+        assert_eq!(run(&[0x00, 0x63, 0x65, 0x68]), Err(ScriptError::BadOpcode));
+
+        assert_eq!(run(&[0x51, 0x63]), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(run(&[0x67]), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(run(&[0x68]), Err(ScriptError::UnbalancedConditional));
+        assert_eq!(run(&[0x63, 0x68]), Err(ScriptError::UnbalancedConditional));
+    }
+
+    #[test]
+    fn stack_operations() {
+        // 1 2 3 ROT
+        assert_eq!(
+            run(&[0x51, 0x52, 0x53, 0x7b]).unwrap(),
+            vec![vec![2], vec![3], vec![1]]
+        );
+        // 1 2 3 4 2SWAP
+        assert_eq!(
+            run(&[0x51, 0x52, 0x53, 0x54, 0x72]).unwrap(),
+            vec![vec![3], vec![4], vec![1], vec![2]]
+        );
+        // 1 2 3 4 5 6 2ROT
+        assert_eq!(
+            run(&[0x51, 0x52, 0x53, 0x54, 0x55, 0x56, 0x71]).unwrap(),
+            vec![vec![3], vec![4], vec![5], vec![6], vec![1], vec![2]]
+        );
+        // 1 2 TUCK
+        assert_eq!(
+            run(&[0x51, 0x52, 0x7d]).unwrap(),
+            vec![vec![2], vec![1], vec![2]]
+        );
+        // 1 2 3 2 PICK
+        assert_eq!(
+            run(&[0x51, 0x52, 0x53, 0x52, 0x79]).unwrap(),
+            vec![vec![1], vec![2], vec![3], vec![1]]
+        );
+        // 1 2 3 2 ROLL
+        assert_eq!(
+            run(&[0x51, 0x52, 0x53, 0x52, 0x7a]).unwrap(),
+            vec![vec![2], vec![3], vec![1]]
+        );
+        // 1 TOALTSTACK 2 FROMALTSTACK DEPTH
+        assert_eq!(
+            run(&[0x51, 0x6b, 0x52, 0x6c, 0x74]).unwrap(),
+            vec![vec![2], vec![1], vec![2]]
+        );
+        // 'abc' SIZE
+        assert_eq!(
+            run(&[0x03, 0x61, 0x62, 0x63, 0x82]).unwrap(),
+            vec![b"abc".to_vec(), vec![3]]
+        );
+
+        assert_eq!(run(&[0x51, 0x6e]), Err(ScriptError::InvalidStackOperation));
+        assert_eq!(
+            run(&[0x51, 0x51, 0x79]),
+            Err(ScriptError::InvalidStackOperation)
+        );
+        assert_eq!(run(&[0x6c]), Err(ScriptError::InvalidAltstackOperation));
+        assert_eq!(run(&[0x51; 1000]).unwrap().len(), 1000);
+        assert_eq!(run(&[0x51; 1001]), Err(ScriptError::StackSize));
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            run(&[0x00, 0xa8]).unwrap(),
+            vec![
+                hex::decode("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+                    .unwrap()
+            ]
+        );
+        assert_eq!(
+            run(&[0x00, 0xa7]).unwrap(),
+            vec![hex::decode("da39a3ee5e6b4b0d3255bfef95601890afd80709").unwrap()]
+        );
+        assert_eq!(
+            run(&[0x00, 0xa6]).unwrap(),
+            vec![hex::decode("9c1185a5c5e9fc54612808977ee8f548b2258d31").unwrap()]
+        );
+        assert_eq!(
+            run(&[0x00, 0xa9]).unwrap(),
+            vec![hex::decode("b472a266d0bd89c13706a4132ccfb16f7c3b9fcb").unwrap()]
+        );
+        assert_eq!(
+            run(&[0x00, 0xaa]).unwrap(),
+            vec![
+                hex::decode("5df6e0e2761359d30a8275058e299fcc0381534545f55cf43e41983f5d4c9456")
+                    .unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn failures() {
+        assert_eq!(run(&[0x6a]), Err(ScriptError::OpReturn));
+        assert_eq!(run(&[0x00, 0x69]), Err(ScriptError::Verify));
+        assert_eq!(run(&[0x51, 0x52, 0x88]), Err(ScriptError::EqualVerify));
+        assert_eq!(run(&[0x51, 0x52, 0x9d]), Err(ScriptError::NumEqualVerify));
+        assert_eq!(run(&[0x50]), Err(ScriptError::BadOpcode));
+        assert_eq!(run(&[0xba]), Err(ScriptError::BadOpcode));
+        assert_eq!(run(&[0xc0]), Err(ScriptError::BadOpcode));
+        assert_eq!(run(&[0x51, 0x95]), Err(ScriptError::DisabledOpcode));
+
+        assert_eq!(run(&[0x61; 201]).unwrap(), Stack::new());
+        assert_eq!(run(&[0x61; 202]), Err(ScriptError::OpCount));
+
+        let mut hex_script = vec![0x4d, 0x09, 0x02];
+        hex_script.extend([0x00; 521]);
+        assert_eq!(run(&hex_script), Err(ScriptError::PushSize));
+        assert_eq!(run(&[0x61; 10_001]), Err(ScriptError::ScriptSize));
+    }
+
+    #[test]
+    fn checksig() {
+        let pubkey = [0x02; 33];
+        let signature = [&pubkey[..], &[0x01]].concat();
+
+        let script_pubkey = vec![push(&pubkey), Script::OP_CHECKSIG];
+
+        assert_eq!(
+            verify_script(
+                &[push(&signature)],
+                &script_pubkey,
+                &[],
+                VerifyFlags::NONE,
+                &FakeChecker
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify_script(
+                &[push(&signature)],
+                &script_pubkey,
+                &[],
+                VerifyFlags::NONE,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::EvalFalse)
+        );
+    }
+
+    #[test]
+    fn checkmultisig() {
+        let keys = [[0x02; 33], [0x03; 33], [0x04; 33]];
+        let sign = |key: &[u8; 33]| push(&[&key[..], &[0x01]].concat());
+
+        // 2 <key 0> <key 1> <key 2> 3 CHECKMULTISIG
+        let mut script_pubkey = vec![Script::OP_2];
+        script_pubkey.extend(keys.iter().map(|key| push(key)));
+        script_pubkey.extend([Script::OP_3, Script::OP_CHECKMULTISIG]);
+
+        let valid = [
+            vec![Script::OP_FALSE, sign(&keys[0]), sign(&keys[1])],
+            vec![Script::OP_FALSE, sign(&keys[0]), sign(&keys[2])],
+            vec![Script::OP_FALSE, sign(&keys[1]), sign(&keys[2])],
+        ];
+        for script_sig in valid.iter() {
+            assert_eq!(
+                verify_script(
+                    script_sig,
+                    &script_pubkey,
+                    &[],
+                    VerifyFlags::NONE,
+                    &FakeChecker
+                ),
+                Ok(())
+            );
+        }
+
+        let invalid = [
+            // signatures out of order
+            vec![Script::OP_FALSE, sign(&keys[1]), sign(&keys[0])],
+            vec![Script::OP_FALSE, sign(&keys[0]), sign(&keys[0])],
+        ];
+        for script_sig in invalid.iter() {
+            assert_eq!(
+                verify_script(
+                    script_sig,
+                    &script_pubkey,
+                    &[],
+                    VerifyFlags::NONE,
+                    &FakeChecker
+                ),
+                Err(ScriptError::EvalFalse)
+            );
+        }
+
+        // without the dummy element
+        assert_eq!(
+            verify_script(
+                &[sign(&keys[0]), sign(&keys[1])],
+                &script_pubkey,
+                &[],
+                VerifyFlags::NONE,
+                &FakeChecker
+            ),
+            Err(ScriptError::InvalidStackOperation)
+        );
+        // 0 0 CHECKMULTISIG succeeds without any signature
+        assert_eq!(run(&[0x00, 0x00, 0x00, 0xae]).unwrap(), vec![vec![1]]);
+        assert_eq!(
+            run(&[0x00, 0x00, 0x01, 0x15, 0xae]),
+            Err(ScriptError::PubkeyCount)
+        );
+        assert_eq!(
+            run(&[0x00, 0x52, 0x51, 0x51, 0xae]),
+            Err(ScriptError::SigCount)
+        );
+    }
+
+    #[test]
+    fn code_separator_and_find_and_delete() {
+        // Checks that `script_code` is what comes after OP_CODESEPARATOR,
+        // without the signature.
+        struct ScriptCodeChecker(Vec<Script>);
+
+        impl SignatureChecker for ScriptCodeChecker {
+            fn check_sig(
+                &self,
+                _signature: &[u8],
+                _pubkey: &[u8],
+                script_code: &[Script],
+                _sig_version: SigVersion,
+            ) -> bool {
+                script_code == self.0.as_slice()
+            }
+        }
+
+        let pushdata1 =
+            |data| Script::Push(PushBytes::with_encoding(data, PushEncoding::PushData1).unwrap());
+
+        let signature = vec![0x30, 0x01];
+        let script = vec![
+            push(&signature),
+            Script::OP_CODESEPARATOR,
+            pushdata1(signature.clone()),
+            Script::OP_DROP,
+            push(&signature),
+            Script::OP_FALSE,
+            Script::OP_CHECKSIG,
+        ];
+        let expected = vec![
+            pushdata1(vec![0x30, 0x01]),
+            Script::OP_DROP,
+            Script::OP_FALSE,
+            Script::OP_CHECKSIG,
+        ];
+
+        let mut stack = Vec::new();
+        eval_script(
+            &mut stack,
+            &script,
+            VerifyFlags::NONE,
+            SigVersion::Base,
+            &ScriptCodeChecker(expected),
+        )
+        .unwrap();
+        assert_eq!(stack, vec![vec![0x30, 0x01], vec![0x01]]);
+    }
+
+    fn run_with(hex_script: &[u8], flags: VerifyFlags) -> Result<Stack, ScriptError> {
+        let mut stack = Vec::new();
+        eval_script(
+            &mut stack,
+            &hex_script.to_script().unwrap(),
+            flags,
+            SigVersion::Base,
+            &BaseSignatureChecker,
+        )?;
+
+        Ok(stack)
+    }
+
+    fn push(data: &[u8]) -> Script {
+        serialize_push(data).to_script().unwrap().pop().unwrap()
+    }
+
+    #[test]
+    fn verify_flags() {
+        let flags: VerifyFlags = "P2SH,STRICTENC".parse().unwrap();
+        assert_eq!(flags, VerifyFlags::P2SH | VerifyFlags::STRICTENC);
+        assert_eq!(flags.to_string(), "P2SH,STRICTENC");
+        assert!(flags.contains(VerifyFlags::P2SH));
+        assert!(!flags.contains(VerifyFlags::P2SH | VerifyFlags::DERSIG));
+        assert!(flags.intersects(VerifyFlags::P2SH | VerifyFlags::DERSIG));
+
+        assert_eq!("".parse(), Ok(VerifyFlags::NONE));
+        assert_eq!("NONE".parse(), Ok(VerifyFlags::NONE));
+        assert_eq!(VerifyFlags::NONE.to_string(), "NONE");
+        assert_eq!(
+            "P2SH,FOO".parse::<VerifyFlags>(),
+            Err(ParseVerifyFlagsError {
+                flag: "FOO".to_string()
+            })
+        );
+
+        assert!(VerifyFlags::STANDARD.contains(VerifyFlags::CONSENSUS));
+        assert!(!VerifyFlags::CONSENSUS.contains(VerifyFlags::CLEANSTACK));
+        assert_eq!(ScriptError::SigNullFail.code(), "NULLFAIL");
+        assert_eq!(ScriptError::EvalFalse.code(), "EVAL_FALSE");
+    }
+
+    #[test]
+    fn minimal_data() {
+        let flags = VerifyFlags::MINIMALDATA;
+
+        assert_eq!(
+            run_with(&[0x4c, 0x01, 0x07], flags),
+            Err(ScriptError::MinimalData)
+        );
+        assert_eq!(
+            run_with(&[0x01, 0x05], flags),
+            Err(ScriptError::MinimalData)
+        );
+        assert_eq!(
+            run_with(&[0x01, 0x81], flags),
+            Err(ScriptError::MinimalData)
+        );
+        assert_eq!(
+            run_with(&[0x4c, 0x00], flags),
+            Err(ScriptError::MinimalData)
+        );
+        assert_eq!(run_with(&[0x01, 0x11], flags).unwrap(), vec![vec![0x11]]);
+        // pushes in unexecuted branches are not checked
+        assert_eq!(
+            run_with(&[0x00, 0x63, 0x01, 0x05, 0x68], flags).unwrap(),
+            Stack::new()
+        );
+
+        // 0x0100 1ADD
+        assert_eq!(
+            run_with(&[0x02, 0x01, 0x00, 0x8b], flags),
+            Err(ScriptError::UnknownError)
+        );
+        assert_eq!(run(&[0x02, 0x01, 0x00, 0x8b]).unwrap(), vec![vec![2]]);
+        // 0x80 1ADD, a negative zero
+        assert_eq!(
+            run_with(&[0x01, 0x80, 0x8b], flags),
+            Err(ScriptError::UnknownError)
+        );
+        // 0xff00 1ADD needs its 0x00
+        assert_eq!(
+            run_with(&[0x02, 0xff, 0x00, 0x8b], flags).unwrap(),
+            vec![vec![0x00, 0x01]]
+        );
+    }
+
+    #[test]
+    fn signature_encoding() {
+        // R = 1 and S = 1
+        let signature = vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01];
+        let pubkey = vec![0x02; 33];
+        let checksig = |signature: &[u8], pubkey: &[u8], flags| {
+            let mut stack = vec![signature.to_vec(), pubkey.to_vec()];
+            eval_script(
+                &mut stack,
+                &[Script::OP_CHECKSIG],
+                flags,
+                SigVersion::Base,
+                &BaseSignatureChecker,
+            )
+            .map(|_| stack)
+        };
+
+        let flags = VerifyFlags::STRICTENC | VerifyFlags::LOW_S;
+        assert_eq!(checksig(&signature, &pubkey, flags), Ok(vec![vec![]]));
+        assert_eq!(
+            checksig(&signature, &pubkey, flags | VerifyFlags::NULLFAIL),
+            Err(ScriptError::SigNullFail)
+        );
+        assert_eq!(
+            checksig(&[], &pubkey, flags | VerifyFlags::NULLFAIL),
+            Ok(vec![vec![]])
+        );
+
+        let mut bad_hashtype = signature.clone();
+        bad_hashtype[8] = 0x04;
+        assert_eq!(
+            checksig(&bad_hashtype, &pubkey, flags),
+            Err(ScriptError::SigHashtype)
+        );
+        assert_eq!(
+            checksig(&bad_hashtype, &pubkey, VerifyFlags::DERSIG),
+            Ok(vec![vec![]])
+        );
+
+        assert_eq!(
+            checksig(&[0x30, 0x01], &pubkey, VerifyFlags::DERSIG),
+            Err(ScriptError::SigDer)
+        );
+        assert_eq!(
+            checksig(&[0x30, 0x01], &pubkey, VerifyFlags::NONE),
+            Ok(vec![vec![]])
+        );
+        // negative R
+        let mut negative_r = signature.clone();
+        negative_r[4] = 0x81;
+        assert_eq!(
+            checksig(&negative_r, &pubkey, VerifyFlags::DERSIG),
+            Err(ScriptError::SigDer)
+        );
+
+        // S = 0x80.. is more than half the order
+        let mut high_s = vec![0x30, 0x26, 0x02, 0x01, 0x01, 0x02, 0x21, 0x00];
+        high_s.extend([0x80; 32]);
+        high_s.push(0x01);
+        assert_eq!(
+            checksig(&high_s, &pubkey, VerifyFlags::DERSIG),
+            Ok(vec![vec![]])
+        );
+        assert_eq!(
+            checksig(&high_s, &pubkey, VerifyFlags::LOW_S),
+            Err(ScriptError::SigHighS)
+        );
+
+        assert_eq!(
+            checksig(&signature, &[0x05; 33], flags),
+            Err(ScriptError::PubkeyType)
+        );
+        assert_eq!(
+            checksig(&signature, &[0x05; 33], VerifyFlags::NONE),
+            Ok(vec![vec![]])
+        );
+    }
+
+    #[test]
+    fn nulldummy() {
+        // 1 0 0 CHECKMULTISIG
+        assert_eq!(
+            run_with(&[0x51, 0x00, 0x00, 0xae], VerifyFlags::NULLDUMMY),
+            Err(ScriptError::SigNullDummy)
+        );
+        assert_eq!(run(&[0x51, 0x00, 0x00, 0xae]).unwrap(), vec![vec![1]]);
+    }
+
+    #[test]
+    fn lock_time() {
+        // accepts lock times and sequences up to 100
+        struct LockTimeChecker;
+
+        impl SignatureChecker for LockTimeChecker {
+            fn check_sig(&self, _: &[u8], _: &[u8], _: &[Script], _: SigVersion) -> bool {
+                false
+            }
+
+            fn check_lock_time(&self, lock_time: i64) -> bool {
+                lock_time <= 100
+            }
+
+            fn check_sequence(&self, sequence: i64) -> bool {
+                sequence <= 100
+            }
+        }
+
+        let run_lock_time = |hex_script: &[u8], flags| {
+            let mut stack = Vec::new();
+            eval_script(
+                &mut stack,
+                &hex_script.to_script().unwrap(),
+                flags,
+                SigVersion::Base,
+                &LockTimeChecker,
+            )
+        };
+
+        let flags = VerifyFlags::CHECKLOCKTIMEVERIFY | VerifyFlags::CHECKSEQUENCEVERIFY;
+        for opcode in [0xb1, 0xb2] {
+            assert_eq!(run_lock_time(&[0x01, 0x64, opcode], flags), Ok(()));
+            assert_eq!(
+                run_lock_time(&[0x01, 0x65, opcode], flags),
+                Err(ScriptError::UnsatisfiedLocktime)
+            );
+            assert_eq!(
+                run_lock_time(&[0x4f, opcode], flags),
+                Err(ScriptError::NegativeLocktime)
+            );
+            assert_eq!(
+                run_lock_time(&[opcode], flags),
+                Err(ScriptError::InvalidStackOperation)
+            );
+            // NOP2 and NOP3 before the soft forks
+            assert_eq!(
+                run_lock_time(&[0x01, 0x65, opcode], VerifyFlags::NONE),
+                Ok(())
+            );
+            assert_eq!(
+                run_lock_time(&[opcode], VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS),
+                Err(ScriptError::DiscourageUpgradableNops)
+            );
+        }
+
+        // lock times use up to 5 bytes
+        assert_eq!(
+            run_lock_time(&[0x05, 0xff, 0xff, 0xff, 0xff, 0x00, 0xb1], flags),
+            Err(ScriptError::UnsatisfiedLocktime)
+        );
+        // sequences with the disable flag are not checked
+        assert_eq!(
+            run_lock_time(&[0x05, 0x00, 0x00, 0x00, 0x80, 0x00, 0xb2], flags),
+            Ok(())
+        );
+        assert_eq!(
+            run_with(&[0xb0], VerifyFlags::DISCOURAGE_UPGRADABLE_NOPS),
+            Err(ScriptError::DiscourageUpgradableNops)
+        );
+    }
+
+    #[test]
+    fn p2sh() {
+        let p2sh = |redeem_script: &[u8]| {
+            vec![
+                Script::OP_HASH160,
+                push(&hash(Ripemd160::new(), &hash(Sha256::new(), redeem_script))),
+                Script::OP_EQUAL,
+            ]
+        };
+
+        // the redeem script is OP_TRUE
+        let script_pubkey = p2sh(&[0x51]);
+        let script_sig = vec![push(&[0x51])];
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::P2SH,
+                &BaseSignatureChecker
+            ),
+            Ok(())
+        );
+
+        // the redeem script is OP_FALSE, which only matters with P2SH
+        let script_pubkey = p2sh(&[0x00]);
+        let script_sig = vec![push(&[0x00])];
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::NONE,
+                &BaseSignatureChecker
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::P2SH,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::EvalFalse)
+        );
+
+        // the redeem script can be anything, even something that can't be
+        // parsed
+        let script_pubkey = p2sh(&[0x51, 0x4c]);
+        let script_sig = vec![push(&[0x51, 0x4c])];
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::P2SH,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::BadOpcode)
+        );
+
+        let script_pubkey = p2sh(&[0x51]);
+        let script_sig = vec![Script::OP_NOP, push(&[0x51])];
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::P2SH,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::SigPushOnly)
+        );
+        assert_eq!(
+            verify_script(
+                &script_sig,
+                &script_pubkey,
+                &[],
+                VerifyFlags::SIGPUSHONLY,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::SigPushOnly)
+        );
+    }
+
+    #[test]
+    fn clean_stack_and_code_separator() {
+        let flags = VerifyFlags::P2SH | VerifyFlags::WITNESS | VerifyFlags::CLEANSTACK;
+        assert_eq!(
+            verify_script(
+                &[Script::OP_TRUE, Script::OP_TRUE],
+                &[],
+                &[],
+                flags,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::CleanStack)
+        );
+        assert_eq!(
+            verify_script(&[Script::OP_TRUE], &[], &[], flags, &BaseSignatureChecker),
+            Ok(())
+        );
+
+        assert_eq!(
+            run_with(&[0x51, 0xab], VerifyFlags::CONST_SCRIPTCODE),
+            Err(ScriptError::OpCodeSeparator)
+        );
+        // the signature is part of the script code
+        let signature = vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01];
+        let mut stack = vec![signature.clone(), vec![0x02; 33]];
+        assert_eq!(
+            eval_script(
+                &mut stack,
+                &[push(&signature), Script::OP_DROP, Script::OP_CHECKSIG],
+                VerifyFlags::CONST_SCRIPTCODE,
+                SigVersion::Base,
+                &BaseSignatureChecker
+            ),
+            Err(ScriptError::SigFindAndDelete)
+        );
+    }
+
+    #[test]
+    fn witness_v0() {
+        let flags = VerifyFlags::P2SH | VerifyFlags::WITNESS;
+        let verify = |script_sig: &[Script], script_pubkey: &[Script], witness: &[Vec<u8>]| {
+            verify_script(script_sig, script_pubkey, witness, flags, &FakeChecker)
+        };
+
+        // P2WSH of OP_TRUE
+        let witness_script = vec![0x51];
+        let p2wsh = vec![
+            Script::OP_FALSE,
+            push(&hash(Sha256::new(), &witness_script)),
+        ];
+        assert_eq!(
+            verify(&[], &p2wsh, std::slice::from_ref(&witness_script)),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&[], &p2wsh, &[vec![0x52]]),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+        assert_eq!(
+            verify(&[], &p2wsh, &[]),
+            Err(ScriptError::WitnessProgramWitnessEmpty)
+        );
+        assert_eq!(
+            verify(&[], &p2wsh, &[vec![], witness_script.clone()]),
+            Err(ScriptError::CleanStack)
+        );
+        assert_eq!(
+            verify(
+                &[Script::OP_TRUE],
+                &p2wsh,
+                std::slice::from_ref(&witness_script)
+            ),
+            Err(ScriptError::WitnessMalleated)
+        );
+        assert_eq!(
+            verify(
+                &[Script::OP_TRUE],
+                &[],
+                std::slice::from_ref(&witness_script)
+            ),
+            Err(ScriptError::WitnessUnexpected)
+        );
+        // without WITNESS, any witness program is true
+        assert_eq!(
+            verify_script(&[], &p2wsh, &[], VerifyFlags::P2SH, &FakeChecker),
+            Ok(())
+        );
+
+        // P2SH-P2WSH
+        let redeem_script = p2wsh.from_script();
+        let p2sh = vec![
+            Script::OP_HASH160,
+            push(&hash(
+                Ripemd160::new(),
+                &hash(Sha256::new(), &redeem_script),
+            )),
+            Script::OP_EQUAL,
+        ];
+        assert_eq!(
+            verify(
+                &[push(&redeem_script)],
+                &p2sh,
+                std::slice::from_ref(&witness_script)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(
+                &[Script::OP_TRUE, push(&redeem_script)],
+                &p2sh,
+                &[witness_script]
+            ),
+            Err(ScriptError::WitnessMalleatedP2sh)
+        );
+
+        // P2WPKH
+        let pubkey = vec![0x02; 33];
+        let signature = [&pubkey[..], &[0x01]].concat();
+        let p2wpkh = vec![
+            Script::OP_FALSE,
+            push(&hash(Ripemd160::new(), &hash(Sha256::new(), &pubkey))),
+        ];
+        assert_eq!(
+            verify(&[], &p2wpkh, &[signature.clone(), pubkey.clone()]),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&[], &p2wpkh, std::slice::from_ref(&pubkey)),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+
+        let uncompressed = vec![0x04; 65];
+        let p2wpkh = vec![
+            Script::OP_FALSE,
+            push(&hash(Ripemd160::new(), &hash(Sha256::new(), &uncompressed))),
+        ];
+        let witness = [[&uncompressed[..], &[0x01]].concat(), uncompressed.clone()];
+        assert_eq!(verify(&[], &p2wpkh, &witness), Ok(()));
+        assert_eq!(
+            verify_script(
+                &[],
+                &p2wpkh,
+                &witness,
+                flags | VerifyFlags::WITNESS_PUBKEYTYPE,
+                &FakeChecker
+            ),
+            Err(ScriptError::WitnessPubkeyType)
+        );
+
+        let wrong_length = vec![Script::OP_FALSE, push(&[0x01; 21])];
+        assert_eq!(
+            verify(&[], &wrong_length, &[]),
+            Err(ScriptError::WitnessProgramWrongLength)
+        );
+
+        // unknown versions are valid unless discouraged
+        let version_2 = vec![Script::OP_2, push(&[0x01; 32])];
+        assert_eq!(verify(&[], &version_2, &[]), Ok(()));
+        assert_eq!(
+            verify_script(
+                &[],
+                &version_2,
+                &[],
+                flags | VerifyFlags::DISCOURAGE_UPGRADABLE_WITNESS_PROGRAM,
+                &FakeChecker
+            ),
+            Err(ScriptError::DiscourageUpgradableWitnessProgram)
+        );
+    }
+
+    #[test]
+    fn taproot() {
+        // Accepts schnorr signatures starting with the key, and every tweak
+        // of the [0x01; 32] internal key.
+        struct TaprootChecker;
+
+        impl SignatureChecker for TaprootChecker {
+            fn check_sig(&self, _: &[u8], _: &[u8], _: &[Script], _: SigVersion) -> bool {
+                false
+            }
+
+            fn check_schnorr_sig(
+                &self,
+                signature: &[u8],
+                pubkey: &[u8],
+                _sig_version: SigVersion,
+                _exec_data: &ExecData,
+            ) -> bool {
+                &signature[..32] == pubkey
+            }
+
+            fn check_tap_tweak(
+                &self,
+                internal_key: &[u8],
+                _output_key: &[u8],
+                _merkle_root: &[u8; 32],
+                _parity: bool,
+            ) -> bool {
+                internal_key == [0x01; 32]
+            }
+        }
+
+        let flags = VerifyFlags::P2SH | VerifyFlags::WITNESS | VerifyFlags::TAPROOT;
+        let verify = |witness: &[Vec<u8>], flags| {
+            let program = [0xaa; 32];
+            let script_pubkey = vec![Script::OP_TRUE, push(&program)];
+            verify_script(&[], &script_pubkey, witness, flags, &TaprootChecker)
+        };
+
+        // key path
+        let signature = [[0xaa; 32], [0x00; 32]].concat();
+        assert_eq!(verify(std::slice::from_ref(&signature), flags), Ok(()));
+        assert_eq!(
+            verify(&[signature.clone(), vec![0x50, 0x01]], flags),
+            Ok(())
+        );
+        assert_eq!(verify(&[[&signature[..], &[0x01]].concat()], flags), Ok(()));
+        assert_eq!(
+            verify(&[[&signature[..], &[0x00]].concat()], flags),
+            Err(ScriptError::SchnorrSigHashtype)
+        );
+        assert_eq!(
+            verify(&[signature[..63].to_vec()], flags),
+            Err(ScriptError::SchnorrSigSize)
+        );
+        assert_eq!(
+            verify(&[[0xbb; 64].to_vec()], flags),
+            Err(ScriptError::SchnorrSig)
+        );
+        assert_eq!(verify(&[[0xbb; 64].to_vec()], VerifyFlags::WITNESS), Ok(()));
+        assert_eq!(
+            verify(&[], flags),
+            Err(ScriptError::WitnessProgramWitnessEmpty)
+        );
+
+        // script path, <key> CHECKSIG
+        let key = [0xcc; 32];
+        let tapscript = [&[0x20][..], &key, &[0xac]].concat();
+        let control = [&[0xc0][..], &[0x01; 32]].concat();
+        let signature = [key, [0x00; 32]].concat();
+        assert_eq!(
+            verify(
+                &[signature.clone(), tapscript.clone(), control.clone()],
+                flags
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(&[vec![], tapscript.clone(), control.clone()], flags),
+            Err(ScriptError::EvalFalse)
+        );
+        assert_eq!(
+            verify(
+                &[[0xbb; 64].to_vec(), tapscript.clone(), control.clone()],
+                flags
+            ),
+            Err(ScriptError::SchnorrSig)
+        );
+        assert_eq!(
+            verify(
+                &[signature.clone(), tapscript.clone(), [0xc0; 33].to_vec()],
+                flags
+            ),
+            Err(ScriptError::WitnessProgramMismatch)
+        );
+        assert_eq!(
+            verify(
+                &[
+                    signature.clone(),
+                    tapscript.clone(),
+                    [&control[..], &[0x00]].concat()
+                ],
+                flags
+            ),
+            Err(ScriptError::TaprootWrongControlSize)
+        );
+        // with a merkle path
+        let deeper_control = [&control[..], &[0x00; 64]].concat();
+        assert_eq!(
+            verify(
+                &[signature.clone(), tapscript.clone(), deeper_control],
+                flags
+            ),
+            Ok(())
+        );
+
+        // OP_SUCCESSx makes the whole script succeed
+        let success = vec![0x00, 0x50, 0x4c];
+        assert_eq!(verify(&[success.clone(), control.clone()], flags), Ok(()));
+        assert_eq!(
+            verify(
+                &[success, control.clone()],
+                flags | VerifyFlags::DISCOURAGE_OP_SUCCESS
+            ),
+            Err(ScriptError::DiscourageOpSuccess)
+        );
+        // unless the script can't be parsed before it
+        assert_eq!(
+            verify(&[vec![0x4c], control.clone()], flags),
+            Err(ScriptError::BadOpcode)
+        );
+
+        // 0 0 0 CHECKMULTISIG
+        assert_eq!(
+            verify(&[vec![0x00, 0x00, 0x00, 0xae], control.clone()], flags),
+            Err(ScriptError::TapscriptCheckMultisig)
+        );
+        // 2 IF 1 ENDIF
+        assert_eq!(
+            verify(&[vec![0x52, 0x63, 0x51, 0x68], control.clone()], flags),
+            Err(ScriptError::TapscriptMinimalIf)
+        );
+        // 0 <key> CHECKSIGADD 1 NUMEQUAL
+        let checksigadd = [&[0x00, 0x20][..], &key, &[0xba, 0x51, 0x9c]].concat();
+        assert_eq!(
+            verify(&[signature.clone(), checksigadd, control.clone()], flags),
+            Ok(())
+        );
+        // unknown key types are valid unless discouraged
+        let unknown_key = [&[0x21][..], &[0xcc; 33], &[0xac]].concat();
+        assert_eq!(
+            verify(
+                &[signature.clone(), unknown_key.clone(), control.clone()],
+                flags
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            verify(
+                &[signature, unknown_key, control.clone()],
+                flags | VerifyFlags::DISCOURAGE_UPGRADABLE_PUBKEYTYPE
+            ),
+            Err(ScriptError::DiscourageUpgradablePubkeyType)
+        );
+
+        // unknown leaf versions
+        let control = [&[0xc2][..], &[0x01; 32]].concat();
+        assert_eq!(verify(&[vec![0x00], control.clone()], flags), Ok(()));
+        assert_eq!(
+            verify(
+                &[vec![0x00], control],
+                flags | VerifyFlags::DISCOURAGE_UPGRADABLE_TAPROOT_VERSION
+            ),
+            Err(ScriptError::DiscourageUpgradableTaprootVersion)
+        );
+    }
+}
@@ -1,4 +1,28 @@
+#![allow(clippy::wrong_self_convention)]
+
+mod asm;
 mod base58;
+mod builder;
+mod interpreter;
+mod network;
 mod script;
+mod solver;
 
-pub use script::{FromScript, Script};
+pub use asm::{Asm, AsmParseError, AsmParseErrorKind, FromAsm, ToAsm};
+pub use base58::{
+    convert_extended_key, Alphabet, Base58CheckPayload, Base58CheckVersion, ExtendedKeyScriptType,
+    FromBase58, FromBase58Check, FromBase58CheckError, FromBase58Error, ToBase58, ToBase58Check,
+    ToBase58Error,
+};
+pub use builder::ToPush;
+pub use interpreter::{
+    cast_to_bool, eval_script, verify_script, BaseSignatureChecker, ExecData,
+    ParseVerifyFlagsError, ScriptError, ScriptNum, SigVersion, SignatureChecker, Stack,
+    VerifyFlags,
+};
+pub use network::Network;
+pub use script::{
+    FromScript, Instruction, Instructions, PushBytes, PushEncoding, PushEncodingError, Script,
+    ScriptBuf, ScriptParseError, ScriptParseErrorKind, ToScript,
+};
+pub use solver::{solve, TxoutType};
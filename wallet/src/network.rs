@@ -0,0 +1,15 @@
+// Signet and regtest reuse the testnet prefixes for everything that is
+// base58-encoded, so those three can't be told apart from an address or key.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Network {
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    pub fn is_mainnet(&self) -> bool {
+        *self == Network::Bitcoin
+    }
+}
@@ -1,18 +1,19 @@
 use derive_tag::{FromTag, ToTag};
-use std::convert::TryInto;
+use std::iter::FusedIterator;
+use std::{error, fmt};
 use strum_macros::EnumString;
 use tag::{FromTag, ToTag};
 
 #[allow(non_camel_case_types)]
-#[derive(Debug, PartialEq, ToTag, FromTag, EnumString)]
+#[derive(Debug, PartialEq, Clone, ToTag, FromTag, EnumString)]
 #[repr(u8)]
 pub enum Script {
-    // holds bytes pushed to the stack and the value of the amount of bytes that
-    // were supposed to be pushed (in little-endian)
     OP_FALSE = 0x00, // equivalent to OP_0
-    OP_PUSHDATA1 = 0x4c,
-    OP_PUSHDATA2 = 0x4d,
-    OP_PUSHDATA4 = 0x4e,
+    // bytes pushed to the stack, along with how their size was encoded. Its
+    // implicit discriminant (0x01) is never used, the opcode is either the size
+    // itself or one of OP_PUSHDATA1 (0x4c), OP_PUSHDATA2 and OP_PUSHDATA4
+    #[strum(disabled)]
+    Push(PushBytes),
     OP_1NEGATE = 0x4f,
     OP_TRUE = 0x51, // equivalent to OP_1
     OP_2 = 0x52,
@@ -59,20 +60,35 @@ pub enum Script {
     OP_SWAP = 0x7c,
     OP_TUCK = 0x7d,
 
+    OP_CAT = 0x7e,
+    OP_SUBSTR = 0x7f,
+    OP_LEFT = 0x80,
+    OP_RIGHT = 0x81,
     OP_SIZE = 0x82,
 
+    OP_INVERT = 0x83,
+    OP_AND = 0x84,
+    OP_OR = 0x85,
+    OP_XOR = 0x86,
+
     OP_EQUAL = 0x87,
     OP_EQUALVERIFY = 0x88,
 
     OP_1ADD = 0x8b,
     OP_1SUB = 0x8c,
-
+    OP_2MUL = 0x8d,
+    OP_2DIV = 0x8e,
     OP_NEGATE = 0x8f,
     OP_ABS = 0x90,
     OP_NOT = 0x91,
     OP_0NOTEQUAL = 0x92,
     OP_ADD = 0x93,
     OP_SUB = 0x94,
+    OP_MUL = 0x95,
+    OP_DIV = 0x96,
+    OP_MOD = 0x97,
+    OP_LSHIFT = 0x98,
+    OP_RSHIFT = 0x99,
 
     OP_BOOLAND = 0x9a,
     OP_BOOLOR = 0x9b,
@@ -101,6 +117,13 @@ pub enum Script {
     OP_CHECKLOCKTIMEVERIFY = 0xb1,
     OP_CHECKSEQUENCEVERIFY = 0xb2,
 
+    OP_CHECKSIGADD = 0xba,
+    // any byte in 0xbb..=0xfc, none of them has been assigned an opcode (they
+    // are OP_SUCCESSx in tapscript). Like Push, its discriminant (0xbb) is
+    // never used, the opcode is the value it holds
+    #[strum(disabled)]
+    OP_UNKNOWN(u8),
+
     OP_PUBKEYHASH = 0xfd,
     OP_PUBKEY = 0xfe,
     OP_INVALIDOPCODE = 0xff,
@@ -120,136 +143,322 @@ pub enum Script {
     OP_NOP8 = 0xb7,
     OP_NOP9 = 0xb8,
     OP_NOP10 = 0xb9,
-
-    // this has an arbitrary (unused) u8 value, but such value will never be used
-    Data(Vec<u8>, Vec<u8>),
 }
 
 impl Script {
-    fn is_data(&self) -> bool {
+    pub(crate) fn to_opcode(&self) -> u8 {
         match &self {
-            Self::Data(_, _) => true,
-            _ => false,
+            Script::Push(push) => push.opcode(),
+            Script::OP_UNKNOWN(opcode) => *opcode,
+            _ => self.to_tag(),
         }
     }
+}
 
-    fn get_data_info(&self) -> (Vec<u8>, Vec<u8>) {
-        match &self {
-            Self::Data(size, data) => (size.to_vec(), data.to_vec()),
-            _ => panic!("Method should be called on Script::Data only."),
+// How the size of a push is encoded: as the opcode itself, or after one of the
+// OP_PUSHDATAx in 1, 2 or 4 little-endian bytes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PushEncoding {
+    Direct,
+    PushData1,
+    PushData2,
+    PushData4,
+}
+
+impl PushEncoding {
+    // The smallest encoding that can push `size` bytes. Empty data needs an
+    // OP_PUSHDATAx, it should be pushed with OP_FALSE instead.
+    pub fn for_size(size: usize) -> PushEncoding {
+        match size {
+            1..=0x4b => PushEncoding::Direct,
+            0..=0xff => PushEncoding::PushData1,
+            0x100..=0xffff => PushEncoding::PushData2,
+            _ => PushEncoding::PushData4,
         }
     }
 
-    fn is_pushdata_op(&self) -> bool {
-        match &self {
-            Self::OP_PUSHDATA1 | Self::OP_PUSHDATA2 | Self::OP_PUSHDATA4 => true,
-            _ => false,
+    // Whether `size` bytes can be pushed with this encoding.
+    pub fn fits(self, size: usize) -> bool {
+        match self {
+            PushEncoding::Direct => (1..=0x4b).contains(&size),
+            PushEncoding::PushData1 => size <= 0xff,
+            PushEncoding::PushData2 => size <= 0xffff,
+            PushEncoding::PushData4 => size <= u32::MAX as usize,
         }
     }
+}
 
-    fn get_data_for_pushdata(&self, hex_script: &[u8]) -> (usize, Self) {
-        match &self {
-            Self::OP_PUSHDATA1 => {
-                let data_size = hex_script[0];
+// Data pushed to the stack. The encoding is kept so scripts that don't use
+// the smallest push serialize back to the same bytes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PushBytes {
+    data: Vec<u8>,
+    encoding: PushEncoding,
+}
 
-                let total_to_skip = 1 + data_size as usize;
-                let size_bytes = vec![data_size];
+impl PushBytes {
+    // Pushes `data` with the smallest encoding that fits it. Empty data is
+    // pushed with OP_FALSE, only `with_encoding` can push it otherwise.
+    pub fn new(data: Vec<u8>) -> Option<PushBytes> {
+        if data.is_empty() {
+            return None;
+        }
 
-                (
-                    total_to_skip,
-                    Self::Data(size_bytes, hex_script[1..=data_size as usize].to_vec()),
-                )
-            }
-            Self::OP_PUSHDATA2 => {
-                let data_size_bytes = &hex_script[..2];
-                let data_size = u16::from_le_bytes(data_size_bytes.try_into().unwrap());
-
-                let total_to_skip = 2 + data_size as usize;
-                let size_bytes = data_size_bytes.to_vec();
-
-                (
-                    total_to_skip,
-                    Self::Data(
-                        size_bytes,
-                        hex_script[2..=(1 + data_size) as usize].to_vec(),
-                    ),
-                )
-            }
-            Self::OP_PUSHDATA4 => {
-                let data_size_bytes = &hex_script[..4];
-                let data_size = u32::from_le_bytes(data_size_bytes.try_into().unwrap());
-
-                let total_to_skip = 4 + data_size as usize;
-                let size_bytes = data_size_bytes.to_vec();
-
-                (
-                    total_to_skip,
-                    Self::Data(
-                        size_bytes,
-                        hex_script[4..=(3 + data_size) as usize].to_vec(),
-                    ),
-                )
-            }
-            _ => panic!("Not a OP_PUSHDATAx op."),
+        let encoding = PushEncoding::for_size(data.len());
+        Some(PushBytes { data, encoding })
+    }
+
+    // Pushes `data` with a given encoding, which doesn't need to be the
+    // smallest one.
+    pub fn with_encoding(
+        data: Vec<u8>,
+        encoding: PushEncoding,
+    ) -> Result<PushBytes, PushEncodingError> {
+        if !encoding.fits(data.len()) {
+            return Err(PushEncodingError {
+                size: data.len(),
+                encoding,
+            });
         }
+
+        Ok(PushBytes { data, encoding })
     }
 
-    fn to_opcode(&self) -> u8 {
-        match &self {
-            Script::Data(_, _) => panic!("This does not have a specific opcode."),
-            _ => self.to_tag(),
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn encoding(&self) -> PushEncoding {
+        self.encoding
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    // Whether no shorter push could push the same data, never the case of
+    // empty data since OP_FALSE pushes it in one byte. MINIMALDATA also wants a
+    // few single bytes pushed with OP_1..OP_16 or OP_1NEGATE instead.
+    pub fn is_minimal_encoding(&self) -> bool {
+        !self.data.is_empty() && self.encoding == PushEncoding::for_size(self.data.len())
+    }
+
+    fn opcode(&self) -> u8 {
+        match self.encoding {
+            PushEncoding::Direct => self.data.len() as u8,
+            PushEncoding::PushData1 => 0x4c,
+            PushEncoding::PushData2 => 0x4d,
+            PushEncoding::PushData4 => 0x4e,
         }
     }
 
-    fn opcode_to_script(opcode: u8, hex_script: &[u8]) -> Self {
-        match opcode {
-            0x01..=0x4b => Self::Data(vec![opcode], hex_script[..opcode as usize].to_vec()),
-            _ => Script::from_tag(opcode),
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        let size = self.data.len();
+
+        bytes.push(self.opcode());
+        match self.encoding {
+            PushEncoding::Direct => {}
+            PushEncoding::PushData1 => bytes.push(size as u8),
+            PushEncoding::PushData2 => bytes.extend((size as u16).to_le_bytes()),
+            PushEncoding::PushData4 => bytes.extend((size as u32).to_le_bytes()),
         }
+        bytes.extend(&self.data);
     }
 }
 
-trait ToScript {
-    fn to_script(&self) -> Vec<Script>;
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PushEncodingError {
+    pub size: usize,
+    pub encoding: PushEncoding,
 }
 
-impl ToScript for Vec<u8> {
-    fn to_script(&self) -> Vec<Script> {
-        let mut script: Vec<Script> = Vec::new();
+impl fmt::Display for PushEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't push {} bytes with {:?}", self.size, self.encoding)
+    }
+}
+
+impl error::Error for PushEncodingError {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ScriptParseErrorKind {
+    // the script ends in the middle of the size of an OP_PUSHDATAx
+    TruncatedPushLength,
+    // the script ends before all the bytes a push announced
+    TruncatedPushData,
+}
 
-        let mut skip = false;
-        let mut skip_until = 0;
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ScriptParseError {
+    // offset of the opcode that could not be parsed
+    pub offset: usize,
+    pub kind: ScriptParseErrorKind,
+}
 
-        for (i, &byte) in self.iter().enumerate() {
-            if skip && i <= skip_until {
-                continue;
+impl fmt::Display for ScriptParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ScriptParseErrorKind::TruncatedPushLength => {
+                write!(f, "truncated push length at offset {}", self.offset)
             }
+            ScriptParseErrorKind::TruncatedPushData => {
+                write!(f, "truncated push data at offset {}", self.offset)
+            }
+        }
+    }
+}
 
-            let s = Script::opcode_to_script(byte, &self[(i + 1)..]);
+impl error::Error for ScriptParseError {}
 
-            if s.is_pushdata_op() {
-                let (total_to_skip, data) = s.get_data_for_pushdata(&self[(i + 1)..]);
+pub trait ToScript {
+    fn to_script(&self) -> Result<Vec<Script>, ScriptParseError>;
+}
 
-                script.push(s);
-                script.push(data);
+// An instruction that borrows the data it pushes from the script bytes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Instruction<'a> {
+    // any opcode other than a push, OP_FALSE and OP_1NEGATE..OP_16 included
+    Op(Script),
+    Push(&'a [u8], PushEncoding),
+}
 
-                skip = true;
-                skip_until = i + total_to_skip;
-            } else if s.is_data() {
-                skip = true;
-                let (mut size, _) = s.get_data_info();
+impl Instruction<'_> {
+    // Copies the data of a push into a `Script`.
+    pub fn to_op(&self) -> Script {
+        match self {
+            Instruction::Op(op) => op.clone(),
+            Instruction::Push(data, encoding) => Script::Push(PushBytes {
+                data: data.to_vec(),
+                encoding: *encoding,
+            }),
+        }
+    }
+}
 
-                size.extend(vec![0; 8 - size.len()]);
-                skip_until = i + usize::from_le_bytes(size.try_into().unwrap());
+// Parses the instruction starting with `opcode`, `hex_script` being the bytes
+// after it. Returns how many of them were consumed.
+fn parse_instruction(
+    opcode: u8,
+    hex_script: &[u8],
+) -> Result<(usize, Instruction<'_>), ScriptParseErrorKind> {
+    let (encoding, size_length, data_size) = match opcode {
+        0x01..=0x4b => (PushEncoding::Direct, 0, opcode as usize),
+        0x4c..=0x4e => {
+            let (encoding, size_length) = match opcode {
+                0x4c => (PushEncoding::PushData1, 1),
+                0x4d => (PushEncoding::PushData2, 2),
+                _ => (PushEncoding::PushData4, 4),
+            };
+
+            let size_bytes = hex_script
+                .get(..size_length)
+                .ok_or(ScriptParseErrorKind::TruncatedPushLength)?;
+
+            let mut data_size = [0; 8];
+            data_size[..size_length].copy_from_slice(size_bytes);
+            (
+                encoding,
+                size_length,
+                u64::from_le_bytes(data_size) as usize,
+            )
+        }
+        0xbb..=0xfc => return Ok((0, Instruction::Op(Script::OP_UNKNOWN(opcode)))),
+        _ => return Ok((0, Instruction::Op(Script::from_tag(opcode)))),
+    };
 
-                script.push(s);
-            } else {
-                skip = false;
-                script.push(s);
+    let data = hex_script
+        .get(size_length..)
+        .and_then(|rest| rest.get(..data_size))
+        .ok_or(ScriptParseErrorKind::TruncatedPushData)?;
+
+    Ok((size_length + data_size, Instruction::Push(data, encoding)))
+}
+
+// Iterates over the instructions of script bytes along with their offsets,
+// without copying what they push. Ends after the first error.
+#[derive(Debug, Clone)]
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    failed: bool,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(bytes: &'a [u8]) -> Instructions<'a> {
+        Instructions {
+            bytes,
+            offset: 0,
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(usize, Instruction<'a>), ScriptParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let offset = self.offset;
+        let &opcode = self.bytes.get(offset)?;
+
+        match parse_instruction(opcode, &self.bytes[(offset + 1)..]) {
+            Ok((consumed, instruction)) => {
+                self.offset += 1 + consumed;
+                Some(Ok((offset, instruction)))
+            }
+            Err(kind) => {
+                self.failed = true;
+                Some(Err(ScriptParseError { offset, kind }))
             }
         }
+    }
+}
+
+impl FusedIterator for Instructions<'_> {}
+
+// The bytes of a script, for when its instructions are only looked at and
+// copying every push into a `Script` isn't needed.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScriptBuf(Vec<u8>);
+
+impl ScriptBuf {
+    pub fn new(bytes: Vec<u8>) -> ScriptBuf {
+        ScriptBuf(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(&self.0)
+    }
+}
 
-        script
+impl From<Vec<u8>> for ScriptBuf {
+    fn from(bytes: Vec<u8>) -> ScriptBuf {
+        ScriptBuf(bytes)
+    }
+}
+
+impl From<&[Script]> for ScriptBuf {
+    fn from(script: &[Script]) -> ScriptBuf {
+        ScriptBuf(script.from_script())
+    }
+}
+
+impl ToScript for [u8] {
+    fn to_script(&self) -> Result<Vec<Script>, ScriptParseError> {
+        Instructions::new(self)
+            .map(|instruction| instruction.map(|(_, instruction)| instruction.to_op()))
+            .collect()
     }
 }
 
@@ -257,17 +466,14 @@ pub trait FromScript {
     fn from_script(&self) -> Vec<u8>;
 }
 
-impl FromScript for Vec<Script> {
+impl FromScript for [Script] {
     fn from_script(&self) -> Vec<u8> {
         let mut hex_script: Vec<u8> = Vec::new();
 
         for opcode in self.iter() {
-            if opcode.is_data() {
-                let (size, data) = opcode.get_data_info();
-                hex_script.extend(size);
-                hex_script.extend(data);
-            } else {
-                hex_script.push(opcode.to_opcode());
+            match opcode {
+                Script::Push(push) => push.write_to(&mut hex_script),
+                _ => hex_script.push(opcode.to_opcode()),
             }
         }
 
@@ -280,6 +486,7 @@ mod tests {
     // Test cases from the following sources:
     // - https://github.com/bitcoin/bitcoin/blob/7fcf53f7b4524572d1d0c9a5fdc388e87eb02416/src/test/data/script_tests.json
     // - https://en.bitcoin.it/wiki/Script#Script_examples
+    // The cases from script_tests.json itself run in tests/script_tests.rs
 
     use super::*;
 
@@ -292,12 +499,12 @@ mod tests {
         let script = vec![
             Script::OP_DUP,
             Script::OP_HASH160,
-            Script::Data(
-                vec![0x14],
-                vec![
+            Script::Push(
+                PushBytes::new(vec![
                     0x89, 0xAB, 0xCD, 0xEF, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB,
                     0xBA, 0xAB, 0xBA, 0xAB, 0xBA, 0xAB, 0xBA,
-                ],
+                ])
+                .unwrap(),
             ),
             Script::OP_EQUALVERIFY,
             Script::OP_CHECKSIG,
@@ -309,7 +516,7 @@ mod tests {
     #[test]
     fn to_script() {
         let (hex_script, expected_script) = get_test_data();
-        assert_eq!(hex_script.to_script(), expected_script);
+        assert_eq!(hex_script.to_script().unwrap(), expected_script);
     }
 
     #[test]
@@ -318,36 +525,270 @@ mod tests {
         assert_eq!(script.from_script(), expected_hex_script);
     }
 
+    fn push(data: Vec<u8>, encoding: PushEncoding) -> Script {
+        Script::Push(PushBytes::with_encoding(data, encoding).unwrap())
+    }
+
     #[test]
     fn op_pushdata1() {
-        let script = vec![Script::OP_PUSHDATA1, Script::Data(vec![0x01], vec![0x07])];
+        let script = vec![push(vec![0x07], PushEncoding::PushData1)];
         let hex_script: Vec<u8> = vec![0x4c, 0x01, 0x07];
 
         assert_eq!(script.from_script(), hex_script);
-        assert_eq!(hex_script.to_script(), script);
+        assert_eq!(hex_script.to_script().unwrap(), script);
     }
 
     #[test]
     fn op_pushdata2() {
-        let script = vec![
-            Script::OP_PUSHDATA2,
-            Script::Data(vec![0x01, 0x00], vec![0x08]),
-        ];
+        let script = vec![push(vec![0x08], PushEncoding::PushData2)];
         let hex_script: Vec<u8> = vec![0x4d, 0x01, 0x00, 0x08];
 
         assert_eq!(script.from_script(), hex_script);
-        assert_eq!(hex_script.to_script(), script);
+        assert_eq!(hex_script.to_script().unwrap(), script);
     }
 
     #[test]
     fn op_pushdata4() {
+        let script = vec![push(vec![0x09], PushEncoding::PushData4)];
+        let hex_script: Vec<u8> = vec![0x4e, 0x01, 0x00, 0x00, 0x00, 0x09];
+
+        assert_eq!(script.from_script(), hex_script);
+        assert_eq!(hex_script.to_script().unwrap(), script);
+    }
+
+    #[test]
+    fn push_encodings() {
+        assert_eq!(
+            PushBytes::new(vec![0x01]).unwrap().encoding(),
+            PushEncoding::Direct
+        );
+        assert_eq!(
+            PushBytes::new(vec![0x01; 0x4b]).unwrap().encoding(),
+            PushEncoding::Direct
+        );
+        assert_eq!(
+            PushBytes::new(vec![0x01; 0x4c]).unwrap().encoding(),
+            PushEncoding::PushData1
+        );
+        assert_eq!(
+            PushBytes::new(vec![0x01; 0x100]).unwrap().encoding(),
+            PushEncoding::PushData2
+        );
+        assert_eq!(
+            PushBytes::new(vec![0x01; 0x10000]).unwrap().encoding(),
+            PushEncoding::PushData4
+        );
+        // OP_FALSE pushes nothing, PushData1 is the smallest encoding that can
+        // push it otherwise
+        assert_eq!(PushBytes::new(vec![]), None);
+        let empty = PushBytes::with_encoding(vec![], PushEncoding::PushData1).unwrap();
+        assert_eq!([Script::Push(empty)].from_script(), vec![0x4c, 0x00]);
+        assert!(!PushBytes::with_encoding(vec![], PushEncoding::PushData1)
+            .unwrap()
+            .is_minimal_encoding());
+
+        assert!(PushBytes::new(vec![0x01; 0x4c])
+            .unwrap()
+            .is_minimal_encoding());
+        assert!(
+            !PushBytes::with_encoding(vec![0x01], PushEncoding::PushData2)
+                .unwrap()
+                .is_minimal_encoding()
+        );
+
+        assert_eq!(
+            PushBytes::with_encoding(vec![], PushEncoding::Direct),
+            Err(PushEncodingError {
+                size: 0,
+                encoding: PushEncoding::Direct
+            })
+        );
+        assert_eq!(
+            PushBytes::with_encoding(vec![0x01; 0x4c], PushEncoding::Direct)
+                .unwrap_err()
+                .to_string(),
+            "can't push 76 bytes with Direct"
+        );
+        assert!(PushBytes::with_encoding(vec![0x01; 0x100], PushEncoding::PushData1).is_err());
+        assert!(PushBytes::with_encoding(vec![0x01; 0x10000], PushEncoding::PushData2).is_err());
+    }
+
+    #[test]
+    fn non_minimal_pushes_round_trip() {
+        // a single byte, and no data at all, with each OP_PUSHDATAx
+        let cases: [&[u8]; 6] = [
+            &[0x4c, 0x01, 0x07],
+            &[0x4d, 0x01, 0x00, 0x07],
+            &[0x4e, 0x01, 0x00, 0x00, 0x00, 0x07],
+            &[0x4c, 0x00],
+            &[0x4d, 0x00, 0x00],
+            &[0x4e, 0x00, 0x00, 0x00, 0x00],
+        ];
+
+        for hex_script in cases.iter() {
+            let script = hex_script.to_script().unwrap();
+            assert_eq!(script.len(), 1);
+            assert_eq!(script.from_script(), *hex_script);
+        }
+
+        assert_eq!(
+            [0x4d, 0x00, 0x00].to_script().unwrap(),
+            vec![push(vec![], PushEncoding::PushData2)]
+        );
+    }
+
+    #[test]
+    fn to_script_errors() {
+        let cases: [(&[u8], ScriptParseError); 5] = [
+            (
+                &[0x4c],
+                ScriptParseError {
+                    offset: 0,
+                    kind: ScriptParseErrorKind::TruncatedPushLength,
+                },
+            ),
+            (
+                &[0x14, 0x01],
+                ScriptParseError {
+                    offset: 0,
+                    kind: ScriptParseErrorKind::TruncatedPushData,
+                },
+            ),
+            (
+                &[0x76, 0x4d, 0x01],
+                ScriptParseError {
+                    offset: 1,
+                    kind: ScriptParseErrorKind::TruncatedPushLength,
+                },
+            ),
+            (
+                &[0x76, 0xa9, 0x4d, 0x02, 0x00, 0x01],
+                ScriptParseError {
+                    offset: 2,
+                    kind: ScriptParseErrorKind::TruncatedPushData,
+                },
+            ),
+            (
+                &[0x4e, 0xff, 0xff, 0xff, 0xff, 0x01],
+                ScriptParseError {
+                    offset: 0,
+                    kind: ScriptParseErrorKind::TruncatedPushData,
+                },
+            ),
+        ];
+
+        for (hex_script, error) in cases.iter() {
+            assert_eq!(hex_script.to_script().unwrap_err(), *error);
+        }
+
+        assert_eq!(
+            [0x4c, 0x02, 0x01].to_script().unwrap_err().to_string(),
+            "truncated push data at offset 0"
+        );
+        assert_eq!(Vec::<u8>::new().to_script().unwrap(), vec![]);
+    }
+
+    #[test]
+    fn instructions() {
+        let (hex_script, script) = get_test_data();
+        let instructions: Vec<_> = Instructions::new(&hex_script)
+            .map(|instruction| instruction.unwrap())
+            .collect();
+
+        assert_eq!(
+            instructions
+                .iter()
+                .map(|(offset, _)| *offset)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 23, 24]
+        );
+        assert_eq!(instructions[0].1, Instruction::Op(Script::OP_DUP));
+        assert_eq!(
+            instructions[2].1,
+            Instruction::Push(&hex_script[3..23], PushEncoding::Direct)
+        );
+        // pushes point into the script instead of holding a copy
+        match instructions[2].1 {
+            Instruction::Push(data, _) => assert!(std::ptr::eq(data, &hex_script[3..23])),
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            instructions
+                .iter()
+                .map(|(_, instruction)| instruction.to_op())
+                .collect::<Vec<_>>(),
+            script
+        );
+
+        let hex_script = [0x4d, 0x01, 0x00, 0x07, 0x00, 0x14, 0x01];
+        let mut instructions = Instructions::new(&hex_script);
+        assert_eq!(
+            instructions.next(),
+            Some(Ok((0, Instruction::Push(&[0x07], PushEncoding::PushData2))))
+        );
+        assert_eq!(
+            instructions.next(),
+            Some(Ok((4, Instruction::Op(Script::OP_FALSE))))
+        );
+        assert_eq!(
+            instructions.next(),
+            Some(Err(ScriptParseError {
+                offset: 5,
+                kind: ScriptParseErrorKind::TruncatedPushData
+            }))
+        );
+        assert_eq!(instructions.next(), None);
+    }
+
+    #[test]
+    fn script_buf() {
+        let (hex_script, script) = get_test_data();
+        let script_buf = ScriptBuf::from(script.as_slice());
+
+        assert_eq!(script_buf.as_bytes(), hex_script.as_slice());
+        assert_eq!(script_buf.instructions().count(), 5);
+        assert_eq!(
+            ScriptBuf::new(vec![0x4c]).instructions().next(),
+            Some(Err(ScriptParseError {
+                offset: 0,
+                kind: ScriptParseErrorKind::TruncatedPushLength
+            }))
+        );
+        assert_eq!(ScriptBuf::default().instructions().next(), None);
+        assert_eq!(script_buf.into_bytes(), hex_script);
+    }
+
+    #[test]
+    fn every_opcode_round_trips() {
+        for opcode in 0..=u8::MAX {
+            // pushes need their data (and size) to be parsed
+            let hex_script = match opcode {
+                0x01..=0x4b => [vec![opcode], vec![0xab; opcode as usize]].concat(),
+                0x4c => vec![opcode, 0x01, 0xab],
+                0x4d => vec![opcode, 0x01, 0x00, 0xab],
+                0x4e => vec![opcode, 0x01, 0x00, 0x00, 0x00, 0xab],
+                _ => vec![opcode],
+            };
+
+            let script = hex_script.to_script().unwrap();
+            assert_eq!(script.from_script(), hex_script);
+        }
+    }
+
+    #[test]
+    fn disabled_and_unknown_opcodes() {
+        let hex_script: Vec<u8> = vec![0x7e, 0x95, 0x99, 0xba, 0xbb, 0xfc, 0xfd];
         let script = vec![
-            Script::OP_PUSHDATA4,
-            Script::Data(vec![0x01, 0x00, 0x00, 0x00], vec![0x09]),
+            Script::OP_CAT,
+            Script::OP_MUL,
+            Script::OP_RSHIFT,
+            Script::OP_CHECKSIGADD,
+            Script::OP_UNKNOWN(0xbb),
+            Script::OP_UNKNOWN(0xfc),
+            Script::OP_PUBKEYHASH,
         ];
-        let hex_script: Vec<u8> = vec![0x4e, 0x01, 0x00, 0x00, 0x00, 0x09];
 
+        assert_eq!(hex_script.to_script().unwrap(), script);
         assert_eq!(script.from_script(), hex_script);
-        assert_eq!(hex_script.to_script(), script);
     }
 }
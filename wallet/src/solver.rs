@@ -0,0 +1,326 @@
+use crate::script::{PushEncoding, Script};
+
+// What a scriptPubKey pays to, with what Core's Solver extracts from it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TxoutType {
+    NonStandard,
+    PubKey(Vec<u8>),
+    PubKeyHash([u8; 20]),
+    ScriptHash([u8; 20]),
+    // `required` of the `keys` must sign
+    Multisig { required: u8, keys: Vec<Vec<u8>> },
+    // OP_RETURN followed by pushes only, the output can't be spent
+    NullData,
+    WitnessV0KeyHash([u8; 20]),
+    WitnessV0ScriptHash([u8; 32]),
+    WitnessV1Taproot([u8; 32]),
+    // a witness program of a version without rules yet
+    WitnessUnknown { version: u8, program: Vec<u8> },
+}
+
+// In templates, OP_PUBKEY stands for a push of a public key and OP_PUBKEYHASH
+// for a push of any 20-byte hash
+const P2PK: &[Script] = &[Script::OP_PUBKEY, Script::OP_CHECKSIG];
+const P2PKH: &[Script] = &[
+    Script::OP_DUP,
+    Script::OP_HASH160,
+    Script::OP_PUBKEYHASH,
+    Script::OP_EQUALVERIFY,
+    Script::OP_CHECKSIG,
+];
+const P2SH: &[Script] = &[Script::OP_HASH160, Script::OP_PUBKEYHASH, Script::OP_EQUAL];
+
+// The data of a push that only uses its size as opcode, Core matches P2PK,
+// P2PKH, P2SH and witness programs on bytes so any other push is a different
+// script
+fn direct_push(op: &Script) -> Option<&[u8]> {
+    match op {
+        Script::Push(push) if push.encoding() == PushEncoding::Direct => Some(push.data()),
+        _ => None,
+    }
+}
+
+// Same as CPubKey::ValidSize: 33 bytes for compressed keys, 65 for the others
+fn is_pubkey(data: &[u8]) -> bool {
+    match data.first() {
+        Some(0x02) | Some(0x03) => data.len() == 33,
+        Some(0x04) | Some(0x06) | Some(0x07) => data.len() == 65,
+        _ => false,
+    }
+}
+
+// OP_1 to OP_16
+fn small_integer(op: &Script) -> Option<u8> {
+    match op.to_opcode() {
+        opcode @ 0x51..=0x60 => Some(opcode - 0x50),
+        _ => None,
+    }
+}
+
+// Returns what the placeholders of `template` matched, in order.
+fn match_template<'a>(script: &'a [Script], template: &[Script]) -> Option<Vec<&'a [u8]>> {
+    if script.len() != template.len() {
+        return None;
+    }
+
+    let mut solutions = Vec::new();
+    for (op, expected) in script.iter().zip(template) {
+        match expected {
+            Script::OP_PUBKEY => solutions.push(direct_push(op).filter(|data| is_pubkey(data))?),
+            Script::OP_PUBKEYHASH => {
+                solutions.push(direct_push(op).filter(|data| data.len() == 20)?)
+            }
+            _ if op == expected => {}
+            _ => return None,
+        }
+    }
+
+    Some(solutions)
+}
+
+// A version opcode then a push of 2 to 40 bytes.
+fn witness_program(script: &[Script]) -> Option<(u8, &[u8])> {
+    let (version, program) = match script {
+        [Script::OP_FALSE, program] => (0, program),
+        [version, program] => (small_integer(version)?, program),
+        _ => return None,
+    };
+
+    direct_push(program)
+        .filter(|program| (2..=40).contains(&program.len()))
+        .map(|program| (version, program))
+}
+
+fn is_push_only(script: &[Script]) -> bool {
+    // OP_RESERVED is below OP_16, Core counts it as a push too
+    script.iter().all(|op| op.to_opcode() <= 0x60)
+}
+
+fn match_multisig(script: &[Script]) -> Option<TxoutType> {
+    let (first, rest) = script.split_first()?;
+    let required = small_integer(first)?;
+    let (last, rest) = rest.split_last()?;
+    let (count, keys) = rest.split_last()?;
+
+    if *last != Script::OP_CHECKMULTISIG
+        || small_integer(count)? as usize != keys.len()
+        || (keys.len() as u8) < required
+    {
+        return None;
+    }
+
+    // unlike the other templates, keys may be pushed with any OP_PUSHDATAx
+    let keys = keys
+        .iter()
+        .map(|op| match op {
+            Script::Push(push) if is_pubkey(push.data()) => Some(push.data().to_vec()),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    Some(TxoutType::Multisig { required, keys })
+}
+
+// Core's Solver: tells what kind of output `script_pubkey` is and extracts
+// the hashes or keys it pays to.
+pub fn solve(script_pubkey: &[Script]) -> TxoutType {
+    if let Some(hashes) = match_template(script_pubkey, P2SH) {
+        return TxoutType::ScriptHash(hashes[0].try_into().unwrap());
+    }
+
+    if let Some((version, program)) = witness_program(script_pubkey) {
+        return match (version, program.len()) {
+            (0, 20) => TxoutType::WitnessV0KeyHash(program.try_into().unwrap()),
+            (0, 32) => TxoutType::WitnessV0ScriptHash(program.try_into().unwrap()),
+            (1, 32) => TxoutType::WitnessV1Taproot(program.try_into().unwrap()),
+            (0, _) => TxoutType::NonStandard,
+            _ => TxoutType::WitnessUnknown {
+                version,
+                program: program.to_vec(),
+            },
+        };
+    }
+
+    if let Some((Script::OP_RETURN, data)) = script_pubkey.split_first() {
+        if is_push_only(data) {
+            return TxoutType::NullData;
+        }
+    }
+
+    if let Some(keys) = match_template(script_pubkey, P2PK) {
+        return TxoutType::PubKey(keys[0].to_vec());
+    }
+    if let Some(hashes) = match_template(script_pubkey, P2PKH) {
+        return TxoutType::PubKeyHash(hashes[0].try_into().unwrap());
+    }
+
+    match_multisig(script_pubkey).unwrap_or(TxoutType::NonStandard)
+}
+
+#[cfg(test)]
+mod tests {
+    // Test cases from the following source:
+    // - https://github.com/bitcoin/bitcoin/blob/7fcf53f7b4524572d1d0c9a5fdc388e87eb02416/src/test/script_standard_tests.cpp
+
+    use super::*;
+    use crate::asm::FromAsm;
+
+    const KEY1: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    const KEY2: &str = "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5";
+    const UNCOMPRESSED: &str = "0479be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8";
+    const HASH20: &str = "89abcdefabbaabbaabbaabbaabbaabbaabbaabba";
+    const HASH32: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn solve_asm(asm: &str) -> TxoutType {
+        solve(&asm.from_asm().unwrap())
+    }
+
+    // for pushes ASM can't express, like non-minimal ones
+    fn solve_parse_script(asm: &str) -> TxoutType {
+        solve(&asm.from_parse_script().unwrap())
+    }
+
+    fn bytes(hex_str: &str) -> Vec<u8> {
+        hex::decode(hex_str).unwrap()
+    }
+
+    #[test]
+    fn success() {
+        assert_eq!(
+            solve_asm(&format!("{} OP_CHECKSIG", KEY1)),
+            TxoutType::PubKey(bytes(KEY1))
+        );
+        assert_eq!(
+            solve_asm(&format!("{} OP_CHECKSIG", UNCOMPRESSED)),
+            TxoutType::PubKey(bytes(UNCOMPRESSED))
+        );
+        assert_eq!(
+            solve_asm(&format!(
+                "OP_DUP OP_HASH160 {} OP_EQUALVERIFY OP_CHECKSIG",
+                HASH20
+            )),
+            TxoutType::PubKeyHash(bytes(HASH20).try_into().unwrap())
+        );
+        assert_eq!(
+            solve_asm(&format!("OP_HASH160 {} OP_EQUAL", HASH20)),
+            TxoutType::ScriptHash(bytes(HASH20).try_into().unwrap())
+        );
+        assert_eq!(
+            solve_asm(&format!("1 {} {} 2 OP_CHECKMULTISIG", KEY1, KEY2)),
+            TxoutType::Multisig {
+                required: 1,
+                keys: vec![bytes(KEY1), bytes(KEY2)]
+            }
+        );
+        assert_eq!(
+            solve_asm(&format!(
+                "2 {} {} {} 3 OP_CHECKMULTISIG",
+                KEY1, KEY2, UNCOMPRESSED
+            )),
+            TxoutType::Multisig {
+                required: 2,
+                keys: vec![bytes(KEY1), bytes(KEY2), bytes(UNCOMPRESSED)]
+            }
+        );
+        // multisig keys, unlike other templates, can be pushed with any
+        // OP_PUSHDATAx
+        assert_eq!(
+            solve_parse_script(&format!(
+                "1 0x4c 0x21 0x{} 0x21 0x{} 2 OP_CHECKMULTISIG",
+                KEY1, KEY2
+            )),
+            TxoutType::Multisig {
+                required: 1,
+                keys: vec![bytes(KEY1), bytes(KEY2)]
+            }
+        );
+        assert_eq!(solve_asm("OP_RETURN"), TxoutType::NullData);
+        assert_eq!(
+            solve_parse_script("OP_RETURN 0x01 0x4b 0x4c 0x02 0x0102 0 -1 16 OP_RESERVED"),
+            TxoutType::NullData
+        );
+        assert_eq!(
+            solve_asm(&format!("0 {}", HASH20)),
+            TxoutType::WitnessV0KeyHash(bytes(HASH20).try_into().unwrap())
+        );
+        assert_eq!(
+            solve_asm(&format!("0 {}", HASH32)),
+            TxoutType::WitnessV0ScriptHash(bytes(HASH32).try_into().unwrap())
+        );
+        assert_eq!(
+            solve_asm(&format!("1 {}", HASH32)),
+            TxoutType::WitnessV1Taproot(bytes(HASH32).try_into().unwrap())
+        );
+        assert_eq!(
+            solve_asm(&format!("16 {}", HASH32)),
+            TxoutType::WitnessUnknown {
+                version: 16,
+                program: bytes(HASH32)
+            }
+        );
+        assert_eq!(
+            solve_parse_script("1 0x02 0x0102"),
+            TxoutType::WitnessUnknown {
+                version: 1,
+                program: vec![0x01, 0x02]
+            }
+        );
+        assert_eq!(solve_asm("9 OP_ADD 11 OP_EQUAL"), TxoutType::NonStandard);
+    }
+
+    #[test]
+    fn failure() {
+        // P2PK with a key of the wrong size, or pushed with OP_PUSHDATA1
+        assert_eq!(
+            solve_asm(&format!("{}01 OP_CHECKSIG", KEY1)),
+            TxoutType::NonStandard
+        );
+        assert_eq!(
+            solve_parse_script(&format!("0x4c 0x21 0x{} OP_CHECKSIG", KEY1)),
+            TxoutType::NonStandard
+        );
+        // P2PKH and P2SH with a 21-byte hash
+        assert_eq!(
+            solve_asm(&format!(
+                "OP_DUP OP_HASH160 {}01 OP_EQUALVERIFY OP_CHECKSIG",
+                HASH20
+            )),
+            TxoutType::NonStandard
+        );
+        assert_eq!(
+            solve_asm(&format!("OP_HASH160 {}01 OP_EQUAL", HASH20)),
+            TxoutType::NonStandard
+        );
+        // multisig with m > n, n not matching the keys, or something else
+        // than a key
+        assert_eq!(
+            solve_asm(&format!("2 {} 1 OP_CHECKMULTISIG", KEY1)),
+            TxoutType::NonStandard
+        );
+        assert_eq!(
+            solve_asm(&format!("1 {} 2 OP_CHECKMULTISIG", KEY1)),
+            TxoutType::NonStandard
+        );
+        assert_eq!(
+            solve_asm(&format!("1 {} {} 2 OP_CHECKMULTISIG", KEY1, HASH20)),
+            TxoutType::NonStandard
+        );
+        assert_eq!(solve_asm("0 0 OP_CHECKMULTISIG"), TxoutType::NonStandard);
+        // OP_RETURN followed by something else than pushes
+        assert_eq!(
+            solve_parse_script("OP_RETURN 0x01 0x4b OP_ADD"),
+            TxoutType::NonStandard
+        );
+        // witness v0 with a program of the wrong size, and programs out of
+        // the 2..=40 range
+        assert_eq!(
+            solve_parse_script("0 0x13 0x01010101010101010101010101010101010101"),
+            TxoutType::NonStandard
+        );
+        assert_eq!(solve_parse_script("1 0x01 0x01"), TxoutType::NonStandard);
+        assert_eq!(
+            solve_parse_script(&format!("1 0x29 0x{}{}00", HASH32, &HASH32[..16])),
+            TxoutType::NonStandard
+        );
+    }
+}